@@ -13,15 +13,22 @@
 //! index that is a multiple of **512** (0, 512, 1024, etc...) is a `TableBlock`
 //! containing metadata about the block itself and the 511 blocks after it.
 //! Every other block that is not a `TableBlock` is a [`DataBlock`].
+//!
+//! # Timing
+//!
+//! The cipher only exists to obfuscate the file (the keys are well known), so it doesn't provide
+//! any secrecy. However, with the `constant-time` feature the checksums are compared without
+//! branching on their bits, for the contexts that are wary of timing side channels.
 
 use self::safe_transmute::SafeTransmute;
 use crate::internals::time;
 use core::{
     ffi::{c_uchar, CStr},
     fmt, mem,
-    ops::Deref,
+    ops::{Deref, DerefMut},
     str,
 };
+use std::time::SystemTime;
 
 /// Result type used through this module.
 type Result<T> = core::result::Result<T, ChecksumMismatchError>;
@@ -50,12 +57,25 @@ macro_rules! block_impl {
             }
         }
 
+        impl From<VirtualPage> for $block_ty {
+            /// Interprets already decrypted bytes as this block.
+            fn from(page: VirtualPage) -> Self {
+                page.safe_transmute()
+            }
+        }
+
         impl Deref for $block_ty {
             type Target = $alias;
             fn deref(&self) -> &Self::Target {
                 &self.0
             }
         }
+
+        impl DerefMut for $block_ty {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
     };
 }
 
@@ -145,6 +165,19 @@ impl TableEntry {
     pub const fn next_block(&self) -> u32 {
         self.next_block
     }
+
+    /// Replaces the checksum of this entry, i.e: after the `DataBlock` it belongs to is modified.
+    #[inline]
+    pub fn set_checksum(&mut self, checksum: u32) {
+        self.checksum = checksum;
+    }
+
+    /// Replaces the index of the block that follows the one this entry belongs to; `0` if there
+    /// is none.
+    #[inline]
+    pub fn set_next_block(&mut self, next_block: u32) {
+        self.next_block = next_block;
+    }
 }
 
 // TODO: decrypt_unchecked().
@@ -170,20 +203,23 @@ impl TableBlock {
         fn inner(page: VirtualPage, index: u32) -> Result<TableBlock> {
             let mut data: [u32; 1024] = page.safe_transmute();
 
-            data.iter_mut().fold(index, |prev, curr| {
-                let key = prev ^ *curr ^ mask(prev);
-                mem::replace(curr, key.rotate_left(16))
-            });
+            // Every word is decrypted with the previous (encrypted) one, so all masks can be
+            // computed before decrypting anything.
+            let prevs = prevs(index, &data);
+            let masks = masks(&prevs);
+            for ((curr, prev), mask) in data.iter_mut().zip(prevs).zip(masks) {
+                *curr = (prev ^ *curr ^ mask).rotate_left(16);
+            }
 
             let expected_checksum = mem::take(&mut data[0]);
             let actual_checksum = self::checksum(&data);
 
-            if actual_checksum != expected_checksum {
+            if !checksums_match(actual_checksum, expected_checksum) {
                 return Err(ChecksumMismatchError {
                     actual: actual_checksum,
                     expected: expected_checksum,
                 });
-            };
+            }
 
             data[0] = actual_checksum;
             Ok(data.safe_transmute())
@@ -193,11 +229,15 @@ impl TableBlock {
     }
 
     /// Encrypts the contents of this `TableBlock`.
-
     // NOTE(rev-eng): I can't seriously believe that you are forced to keep
     // track of the index to be able to encrypt a `TableBlock`.
+    //
+    // The checksum of the block itself (the first one) is re-calculated, so entries can be modified
+    // before encrypting it again.
     pub fn encrypt(self, index: u32) -> VirtualPage {
         let mut data: [u32; 1024] = self.safe_transmute();
+        data[0] = 0;
+        data[0] = self::checksum(&data);
 
         data.iter_mut().fold(index, |prev, curr| {
             *curr = prev ^ curr.rotate_left(16) ^ mask(prev);
@@ -262,7 +302,6 @@ impl FatEntry {
     ///
     /// Returns [`None`] if the name does not have valid UTF-8 characters or if
     /// it is the empty string.
-
     // CONST: `find` and `unwrap_or`.
     #[inline]
     pub fn name(&self) -> Option<&str> {
@@ -272,6 +311,37 @@ impl FatEntry {
         (!name.is_empty()).then(|| &name[name.find('.').unwrap_or(0)..])
     }
 
+    /// Replaces the name of this entry.
+    ///
+    /// # Panics
+    ///
+    /// - If `name` is longer than 31 bytes.
+    pub fn set_name(&mut self, name: &str) {
+        assert!(name.len() < self.name.len(), "name is too long");
+
+        self.name = [0; 32];
+        self.name[..name.len()].copy_from_slice(name.as_bytes());
+    }
+
+    /// Creates an (used) entry called `name`, that has no contents yet.
+    ///
+    /// # Panics
+    ///
+    /// - If `name` is longer than 31 bytes.
+    pub(crate) fn new(name: &str, kind: FatKind, filetime: u64) -> Self {
+        let mut entry = Self::zeroed();
+        entry.flags = Self::USED;
+        entry.set_name(name);
+        entry.kind = kind as u8;
+        entry.filetime = filetime;
+        entry
+    }
+
+    /// The [`flags`] that all the (used) entries on my sai files have.
+    ///
+    /// [`flags`]: FatEntry::flags
+    pub(crate) const USED: u32 = 0x8000_0000;
+
     /// Whether this entry is a `FatKind::Folder` or `FatKind::File`.
     ///
     /// Returns [`None`] if it doesn't have valid values for [`FatKind`].
@@ -289,12 +359,12 @@ impl FatEntry {
     /// Depending on the [`kind`] of this entry it will point to the index
     /// where:
     ///
-    /// # FatKind::Folder
+    /// # `FatKind::Folder`
     ///
     /// The next folder is located. It would be non-zero if the folder has more
     /// than 64 entries.
     ///
-    /// # FatKind::File
+    /// # `FatKind::File`
     ///
     /// The bytes of this file are.
     ///
@@ -313,6 +383,24 @@ impl FatEntry {
         self.size
     }
 
+    /// Replaces the [`next_block`] and [`size`] of this entry, i.e: after its contents were moved.
+    ///
+    /// [`next_block`]: FatEntry::next_block
+    /// [`size`]: FatEntry::size
+    #[inline]
+    pub fn set_contents(&mut self, next_block: u32, size: u32) {
+        self.next_block = next_block;
+        self.size = size;
+    }
+
+    /// Replaces the [`filetime`] of this entry.
+    ///
+    /// [`filetime`]: FatEntry::filetime
+    #[inline]
+    pub fn set_filetime(&mut self, filetime: u64) {
+        self.filetime = filetime;
+    }
+
     /// Represents the number of 100-nanosecond intervals since `January 1,
     /// 1601` (UTC).
     ///
@@ -327,6 +415,23 @@ impl FatEntry {
     pub const fn unixtime(&self) -> u64 {
         time::filetime_to_unixtime(self.filetime)
     }
+
+    /// Same as [`unixtime`], but as a [`SystemTime`].
+    ///
+    /// [`unixtime`]: FatEntry::unixtime
+    #[inline]
+    pub fn modified_time(&self) -> SystemTime {
+        time::unixtime_to_system_time(self.unixtime())
+    }
+
+    /// Same as [`unixtime`], but as a [`chrono::DateTime`].
+    ///
+    /// [`unixtime`]: FatEntry::unixtime
+    #[inline]
+    #[cfg(feature = "chrono")]
+    pub fn modified_datetime(&self) -> chrono::DateTime<chrono::Utc> {
+        time::unixtime_to_datetime(self.unixtime())
+    }
 }
 
 #[repr(C, /* PERF: align(4096) */)]
@@ -350,17 +455,20 @@ impl DataBlock {
         fn inner(page: VirtualPage, checksum: u32) -> Result<DataBlock> {
             let mut data: [u32; 1024] = page.safe_transmute();
 
-            data.iter_mut().fold(checksum, |prev, curr| {
-                mem::replace(curr, curr.wrapping_sub(prev ^ mask(prev)))
-            });
+            // Same as with `TableBlock`s, all masks can be computed before decrypting anything.
+            let prevs = prevs(checksum, &data);
+            let masks = masks(&prevs);
+            for ((curr, prev), mask) in data.iter_mut().zip(prevs).zip(masks) {
+                *curr = curr.wrapping_sub(prev ^ mask);
+            }
 
             let actual = self::checksum(&data);
-            if actual != checksum {
+            if !checksums_match(actual, checksum) {
                 return Err(ChecksumMismatchError {
                     actual,
                     expected: checksum,
                 });
-            };
+            }
 
             Ok(data.safe_transmute())
         }
@@ -368,6 +476,43 @@ impl DataBlock {
         inner(bytes.into(), checksum)
     }
 
+    /// Decrypts every `PAGE_SIZE` chunk of `bytes` as a `DataBlock`, using the
+    /// checksum that `checksum` returns for the position (relative to `bytes`)
+    /// of each chunk.
+    ///
+    /// # Error
+    ///
+    /// Returns [`ChecksumMismatchError`] of the first block that failed to be
+    /// decrypted.
+    ///
+    /// # Panics
+    ///
+    /// - If `bytes.len()` is not a multiple of `PAGE_SIZE`.
+    pub fn decrypt_blocks(
+        bytes: &[u8],
+        mut checksum: impl FnMut(usize) -> u32,
+    ) -> Result<Vec<Self>> {
+        assert!(
+            bytes.len().is_multiple_of(PAGE_SIZE),
+            "bytes should be a multiple of PAGE_SIZE"
+        );
+
+        bytes
+            .chunks_exact(PAGE_SIZE)
+            .enumerate()
+            .map(|(idx, chunk)| {
+                let page: [u8; PAGE_SIZE] = chunk.try_into().expect("chunk is PAGE_SIZE long");
+                Self::decrypt(page, checksum(idx))
+            })
+            .collect()
+    }
+
+    /// The checksum of the contents of this `DataBlock`, as it should be found on its
+    /// `TableBlock` entry.
+    pub fn checksum(&self) -> u32 {
+        self::checksum(&self.clone().safe_transmute())
+    }
+
     /// Encrypts the contents of this `DataBlock`.
     ///
     /// If checksum is `None`, then it would be calculated with the data of this
@@ -392,6 +537,24 @@ fn checksum(block: &[u32; 1024]) -> u32 {
     block.iter().fold(0u32, |sum, e| sum.rotate_left(1) ^ e) | 1
 }
 
+/// Compares two checksums; in constant time if the `constant-time` feature is enabled.
+#[inline]
+fn checksums_match(actual: u32, expected: u32) -> bool {
+    if cfg!(feature = "constant-time") {
+        constant_time_eq(actual, expected)
+    } else {
+        actual == expected
+    }
+}
+
+/// Same as `a == b`, but without branching on the bits of `a` or `b`.
+#[inline(never)]
+fn constant_time_eq(a: u32, b: u32) -> bool {
+    let diff = core::hint::black_box(a ^ b);
+    // The most significant bit is only set if `diff` is not 0.
+    ((diff | diff.wrapping_neg()) >> 31) == 0
+}
+
 #[inline]
 fn mask(value: u32) -> u32 {
     (0..=24).step_by(8).fold(0, |sum, idx| {
@@ -400,6 +563,70 @@ fn mask(value: u32) -> u32 {
     })
 }
 
+/// The word that comes before each word of an encrypted `block`, where `first` comes before the
+/// first one.
+#[inline]
+fn prevs(first: u32, block: &[u32; 1024]) -> [u32; 1024] {
+    let mut prevs = [first; 1024];
+    prevs[1..].copy_from_slice(&block[..1023]);
+    prevs
+}
+
+/// Same as calling [`mask`] on every value of `values`.
+///
+/// With the `simd` feature, the S-Box lookups are gathered 8 values at a time ( only on `x86_64`
+/// CPUs that support `AVX2`; otherwise it falls back to [`mask`] ).
+#[inline]
+fn masks(values: &[u32; 1024]) -> [u32; 1024] {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if std::is_x86_feature_detected!("avx2") {
+        // SAFETY: `AVX2` is supported by the current CPU.
+        return unsafe { simd::masks(values) };
+    }
+
+    values.map(mask)
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod simd {
+    use super::USER;
+    use std::arch::x86_64::{
+        _mm256_add_epi32, _mm256_and_si256, _mm256_i32gather_epi32, _mm256_loadu_si256,
+        _mm256_set1_epi32, _mm256_setzero_si256, _mm256_srli_epi32, _mm256_storeu_si256,
+    };
+
+    /// Same as [`super::masks`], but using `AVX2` instructions.
+    ///
+    /// # Safety
+    ///
+    /// The CPU needs to support `AVX2`.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn masks(values: &[u32; 1024]) -> [u32; 1024] {
+        let mut masks = [0; 1024];
+        let byte = _mm256_set1_epi32(0xFF);
+        let sbox = USER.as_ptr().cast::<i32>();
+
+        for (src, dst) in values.chunks_exact(8).zip(masks.chunks_exact_mut(8)) {
+            // SAFETY: Both `src` and `dst` are 8 `u32`s ( 256 bits ) long, and every index is
+            // masked to be on `0..256`, which are all within `USER`.
+            unsafe {
+                let words = _mm256_loadu_si256(src.as_ptr().cast());
+                let lookup =
+                    |shifted| _mm256_i32gather_epi32::<4>(sbox, _mm256_and_si256(shifted, byte));
+
+                let mut sum = _mm256_setzero_si256();
+                sum = _mm256_add_epi32(sum, lookup(words));
+                sum = _mm256_add_epi32(sum, lookup(_mm256_srli_epi32::<8>(words)));
+                sum = _mm256_add_epi32(sum, lookup(_mm256_srli_epi32::<16>(words)));
+                sum = _mm256_add_epi32(sum, lookup(_mm256_srli_epi32::<24>(words)));
+                _mm256_storeu_si256(dst.as_mut_ptr().cast(), sum);
+            }
+        }
+
+        masks
+    }
+}
+
 /// S-Box for .sai `user` generated files.
 const USER: [u32; 256] = [
     0x9913D29E, 0x83F58D3D, 0xD0BE1526, 0x86442EB7, 0x7EC69BFB, 0x89D75F64, 0xFB51B239, 0xFF097C56,
@@ -438,12 +665,11 @@ const USER: [u32; 256] = [
 
 /// Provides [`SafeTransmute`] to be able to transmute between types when it is
 /// statically known that they are compatible.
-
 // TODO(Unavailable): This seems to be the only place where `transmute` is
 // currently used (mostly for convenience, instead of performance). Move this to
 // a `common/internal/utils` module if it deemed useful outside of here.
 mod safe_transmute {
-    use super::*;
+    use super::{mem, DataBlock, FatEntryArray, TableBlock, TableEntryArray, VirtualPage};
 
     // FIXME(Unavailable): These 2 would present different runtime behaviour
     // iff, `Src` and `Dst` are not of even size. I don't think there is a way
@@ -486,7 +712,6 @@ mod safe_transmute {
         Self: Sized,
     {
         /// Reinterprets the bits of a value of one type as another type, safely.
-
         // NIGHTLY: with `transmute_unchecked` I would be able to provide a
         // blanket implementation for this.
         fn safe_transmute(self) -> Dst;
@@ -543,12 +768,12 @@ mod tests {
     const TABLE_INDEX: u32 = 0;
     const ROOT_INDEX: usize = 2;
 
-    #[inline(always)]
+    #[inline]
     fn table() -> [u8; PAGE_SIZE] {
         BYTES[..PAGE_SIZE].try_into().unwrap()
     }
 
-    #[inline(always)]
+    #[inline]
     #[rustfmt::skip]
     fn data() -> [u8; PAGE_SIZE] {
         BYTES[PAGE_SIZE * ROOT_INDEX..][..PAGE_SIZE].try_into().unwrap()
@@ -578,6 +803,40 @@ mod tests {
         assert_eq!(entry.unixtime(), 1567531938); // 09/03/2019 @ 05:32pm
     }
 
+    #[test]
+    fn decrypt_blocks_works() {
+        const INDEX: usize = ROOT_INDEX + 1;
+
+        let table = TableBlock::decrypt(table(), TABLE_INDEX).unwrap();
+        let bytes = &BYTES[PAGE_SIZE * ROOT_INDEX..][..PAGE_SIZE * 2];
+        let blocks =
+            DataBlock::decrypt_blocks(bytes, |idx| table[ROOT_INDEX + idx].checksum()).unwrap();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0][0].name().unwrap(), ".73851dcd1203b24d");
+
+        let page: [u8; PAGE_SIZE] = BYTES[PAGE_SIZE * INDEX..][..PAGE_SIZE].try_into().unwrap();
+        let block = DataBlock::decrypt(page, table[INDEX].checksum()).unwrap();
+        assert_eq!(
+            AsRef::<[u8]>::as_ref(&blocks[1].clone().into_virtual_page()),
+            AsRef::<[u8]>::as_ref(&block.into_virtual_page())
+        );
+    }
+
+    #[test]
+    fn modified_time_works() {
+        let table = TableBlock::decrypt(table(), TABLE_INDEX).unwrap();
+        let data = DataBlock::decrypt(data(), table[ROOT_INDEX].checksum()).unwrap();
+
+        let time = data[0]
+            .modified_time()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(time.as_secs(), 1567531938);
+        #[cfg(feature = "chrono")]
+        assert_eq!(data[0].modified_datetime().timestamp(), 1567531938);
+    }
+
     #[test]
     fn encrypt_works() {
         let table_block = TableBlock::decrypt(table(), TABLE_INDEX).unwrap();
@@ -592,4 +851,36 @@ mod tests {
         let data_block = DataBlock::decrypt(data(), checksum).unwrap();
         assert!(*data_block.encrypt(None) == data());
     }
+
+    #[test]
+    fn masks_agrees_with_mask() {
+        let mut values = [0; 1024];
+        values.iter_mut().fold(0x9E37_79B9u32, |seed, value| {
+            *value = seed;
+            seed.rotate_left(5).wrapping_mul(0x0100_0193) ^ 0xDEAD_BEEF
+        });
+        values[0] = u32::MAX;
+
+        assert_eq!(masks(&values), values.map(mask));
+    }
+
+    #[test]
+    fn constant_time_eq_agrees_with_eq() {
+        let values = [
+            0,
+            1,
+            2,
+            0x7FFF_FFFF,
+            0x8000_0000,
+            0xFFFF_FFFE,
+            u32::MAX,
+            0xDEAD_BEEF,
+        ];
+        for a in values {
+            for b in values {
+                assert_eq!(constant_time_eq(a, b), a == b, "{a:#x} {b:#x}");
+                assert_eq!(checksums_match(a, b), a == b, "{a:#x} {b:#x}");
+            }
+        }
+    }
 }
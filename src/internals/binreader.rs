@@ -25,15 +25,33 @@ where
         Self { inner }
     }
 
+    /// Skips `amt` bytes, without allocating them all at once.
+    ///
+    /// Fails with [`io::ErrorKind::UnexpectedEof`] if the inner reader runs out of bytes.
     #[inline]
     pub fn skip(&mut self, amt: usize) -> io::Result<()> {
-        self.inner.read_exact(&mut vec![0; amt])
+        let skipped = io::copy(&mut (&mut self.inner).take(amt as u64), &mut io::sink())?;
+        if skipped < amt as u64 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
     }
 
+    /// Fills the whole `buf`, for when the amount of bytes to read is only known at runtime (i.e:
+    /// the size of a stream header).
+    ///
+    /// Unlike [`Read::read`], which returns how many bytes were read and can silently stop short,
+    /// this fails with [`io::ErrorKind::UnexpectedEof`] if the inner reader runs out of bytes.
+    #[inline]
+    pub fn read_slice(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.inner.read_exact(buf)
+    }
+
+    /// Reads exactly `N` bytes; see [`BinReader::read_slice`].
     #[inline]
     pub fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
         let mut buf = [0; N];
-        self.inner.read_exact(&mut buf)?;
+        self.read_slice(&mut buf)?;
         Ok(buf)
     }
 
@@ -61,6 +79,32 @@ where
             Err(err) => Some(Err(err)),
         }
     }
+
+    /// Reads the `size` bytes of a stream body, after a [`read_stream_header`] call.
+    ///
+    /// `size` comes from the file itself, so the body grows as it is read, instead of allocating
+    /// `size` bytes upfront; a corrupted header fails with [`io::ErrorKind::UnexpectedEof`] once
+    /// the inner reader runs out of bytes.
+    ///
+    /// [`read_stream_header`]: BinReader::read_stream_header
+    pub fn read_stream_body(&mut self, size: u32) -> io::Result<Box<[u8]>> {
+        let mut body = Vec::new();
+        (&mut self.inner)
+            .take(u64::from(size))
+            .read_to_end(&mut body)?;
+        if body.len() < size as usize {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(body.into_boxed_slice())
+    }
+
+    /// Skips the `size` bytes of a stream body, after a [`read_stream_header`] call.
+    ///
+    /// [`read_stream_header`]: BinReader::read_stream_header
+    #[inline]
+    pub fn skip_stream_body(&mut self, size: u32) -> io::Result<()> {
+        self.skip(size as usize)
+    }
 }
 
 impl<R> Read for BinReader<R>
@@ -77,3 +121,61 @@ where
         self.inner.read_exact(buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const STREAMS: &[u8] = &[
+        b'e', b'm', b'a', b'n', 3, 0, 0, 0, 1, 2, 3,
+        b'b', b'm', b'u', b'n', 2, 0, 0, 0, 4, 5,
+        0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn stream_body_works() -> io::Result<()> {
+        let mut reader = BinReader::new(STREAMS);
+
+        let (tag, size) = reader.read_stream_header::<[u8; 4]>().unwrap()?;
+        assert_eq!(tag, Some(*b"name"));
+        assert_eq!(&*reader.read_stream_body(size)?, &[1, 2, 3]);
+
+        let (tag, size) = reader.read_stream_header::<[u8; 4]>().unwrap()?;
+        assert_eq!(tag, Some(*b"numb"));
+        reader.skip_stream_body(size)?;
+
+        assert!(reader.read_stream_header::<[u8; 4]>().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn huge_stream_bodies_fail_without_allocating() {
+        #[rustfmt::skip]
+        const HUGE: &[u8] = &[b'e', b'm', b'a', b'n', 0xFF, 0xFF, 0xFF, 0xFF, 1, 2, 3];
+
+        let mut reader = BinReader::new(HUGE);
+        let (_, size) = reader.read_stream_header::<[u8; 4]>().unwrap().unwrap();
+        assert_eq!(size, u32::MAX);
+        let err = reader.read_stream_body(size).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut reader = BinReader::new(HUGE);
+        let (_, size) = reader.read_stream_header::<[u8; 4]>().unwrap().unwrap();
+        let err = reader.skip_stream_body(size).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_slice_fails_on_short_reads() {
+        let mut reader = BinReader::new([1, 2, 3].as_slice());
+
+        let mut buf = [0; 2];
+        assert!(reader.read_slice(&mut buf).is_ok());
+        assert_eq!(buf, [1, 2]);
+
+        let err = reader.read_slice(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
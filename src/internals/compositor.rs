@@ -0,0 +1,704 @@
+//! Flattens [`Layer`]s into a single image.
+//!
+//! For now, only the properties that are found on the [`Layer`] header are taken into account;
+//! `textures`, `effects` and `masks` are ignored, and only [`LayerKind::Regular`] layers have
+//! pixels to blend. Layers of an unknown kind ( `_Unknown4` and `_Unknown7` ) are skipped, as if
+//! they were not there.
+
+use crate::{
+    models::layer::{BlendingMode, Layer, LayerBounds, LayerKind},
+    pixel_ops,
+};
+use std::collections::{HashMap, HashSet};
+
+pub(crate) type BlendFn = fn(u8, u8, u8, u8) -> u8;
+pub(crate) type AlphaFn = fn(u8, u8) -> u8;
+
+pub(crate) fn blend_fn(mode: BlendingMode) -> BlendFn {
+    match mode {
+        BlendingMode::Multiply => pixel_ops::multiply,
+        BlendingMode::Screen => pixel_ops::screen,
+        BlendingMode::Overlay => pixel_ops::overlay,
+        BlendingMode::Luminosity => pixel_ops::luminosity,
+        BlendingMode::Shade => pixel_ops::shade,
+        BlendingMode::LumiShade => pixel_ops::lumi_shade,
+        BlendingMode::Binary => |bg, fg, _, fg_a| pixel_ops::binary(bg, fg, fg_a),
+        BlendingMode::PassThrough | BlendingMode::Normal => {
+            |bg, fg, _, fg_a| pixel_ops::normal(bg, fg, fg_a)
+        }
+    }
+}
+
+/// Gets the function that blends the alpha channel ( `bg_a`, `fg_a` ) of a pixel.
+pub(crate) fn alpha_fn(mode: BlendingMode) -> AlphaFn {
+    match mode {
+        BlendingMode::Binary => |bg_a, fg_a| pixel_ops::binary(bg_a, fg_a, fg_a),
+        _ => |bg_a, fg_a| pixel_ops::normal(bg_a, fg_a, fg_a),
+    }
+}
+
+/// Maps the id of every [`LayerKind::Set`] found on `layers` to the set itself.
+pub(crate) fn sets_by_id<'a>(
+    layers: impl IntoIterator<Item = &'a Layer>,
+) -> HashMap<u32, &'a Layer> {
+    layers
+        .into_iter()
+        .filter(|layer| layer.kind == LayerKind::Set)
+        .map(|layer| (layer.id, layer))
+        .collect()
+}
+
+/// Whether the parent chain of the set with the given `id` loops back to the set itself (e.g:
+/// `A -> B -> A`, or `A -> A`); only possible on corrupted files.
+fn is_cyclic(sets: &HashMap<u32, &Layer>, id: u32) -> bool {
+    let mut walked = HashSet::new();
+    let mut parent = sets.get(&id).and_then(|set| set.parent_set);
+    while let Some(set) = parent.and_then(|parent| sets.get(&parent)) {
+        if set.id == id {
+            return true;
+        }
+        if !walked.insert(set.id) {
+            // Loops, but somewhere above `id`.
+            return false;
+        }
+        parent = set.parent_set;
+    }
+    false
+}
+
+/// Iterates over the [`LayerKind::Set`]s ( found on `sets` ) that contain `layer`, from the
+/// innermost to the outermost one.
+///
+/// A set whose parent chain loops back to itself ( see [`is_cyclic`] ) is treated as if it had no
+/// parent, so every set of the loop ends up being top-level, no matter which layer the walk
+/// starts from.
+pub(crate) fn ancestors<'a>(
+    sets: &'a HashMap<u32, &'a Layer>,
+    layer: &Layer,
+) -> impl Iterator<Item = &'a Layer> + 'a {
+    let is_top_level = |layer: &Layer| layer.kind == LayerKind::Set && is_cyclic(sets, layer.id);
+    let mut parent = if is_top_level(layer) {
+        None
+    } else {
+        layer.parent_set
+    };
+    std::iter::from_fn(move || {
+        let set = *sets.get(&parent?)?;
+        parent = if is_top_level(set) {
+            None
+        } else {
+            set.parent_set
+        };
+        Some(set)
+    })
+}
+
+/// Creates a function that tells if a layer is visible, taking into account the visibility of
+/// all the [`LayerKind::Set`]s (found on `layers`) that contain it.
+pub fn visibility(layers: &[Layer]) -> impl Fn(&Layer) -> bool + '_ {
+    let sets = sets_by_id(layers);
+    move |layer| layer.visible && ancestors(&sets, layer).all(|set| set.visible)
+}
+
+/// Flattens `layers` ( ordered from `lowest` to `highest` ) into a `width * height` image, with
+/// pixels in the RGBA color model with pre-multiplied alpha.
+///
+/// Layers that are not visible ( or that are inside of a not visible [`LayerKind::Set`] ), and
+/// layers with an `opacity` of 0 are skipped entirely.
+///
+/// The children of a [`LayerKind::Set`] that is not [`BlendingMode::PassThrough`] (or that has an
+/// `opacity` lower than 100) are flattened on their own, and the result is blended with the
+/// `blending_mode` and `opacity` of the set.
+pub fn composite(width: u32, height: u32, layers: &[Layer]) -> Vec<u8> {
+    let is_visible = visibility(layers);
+    let layers: Vec<_> = layers.iter().collect();
+
+    let mut image = vec![0; width as usize * height as usize * 4];
+    composite_onto(&mut image, (width, height), &layers, &is_visible);
+
+    image
+}
+
+/// Replaces each top-level [`LayerKind::Set`] of `layers` ( ordered from `lowest` to `highest` ),
+/// and everything inside of it, with a single `width * height` [`LayerKind::Regular`] layer
+/// holding the rendered result; see [`Sai::flatten_groups`].
+///
+/// [`Sai::flatten_groups`]: crate::Sai::flatten_groups
+pub(crate) fn flatten_groups(width: u32, height: u32, layers: &[Layer]) -> Vec<Layer> {
+    let sets = sets_by_id(layers);
+    let top_level_set = |layer: &Layer| ancestors(&sets, layer).last().map(|set| set.id);
+
+    let mut flattened = Vec::new();
+    for layer in layers {
+        match (top_level_set(layer), layer.kind) {
+            (None, LayerKind::Set) => {
+                let children: Vec<_> = layers
+                    .iter()
+                    .filter(|child| top_level_set(child) == Some(layer.id))
+                    .cloned()
+                    .collect();
+                let data = composite(width, height, &children);
+
+                flattened.push(Layer {
+                    kind: LayerKind::Regular,
+                    // Places `data_origin` at the top-left corner of the canvas.
+                    bounds: LayerBounds {
+                        x: 8,
+                        y: 8,
+                        width,
+                        height,
+                    },
+                    blending_mode: match layer.blending_mode {
+                        BlendingMode::PassThrough => BlendingMode::Normal,
+                        mode => mode,
+                    },
+                    parent_set: None,
+                    open: None,
+                    data: Some(data),
+                    ..layer.clone()
+                });
+            }
+            (None, _) => flattened.push(Layer {
+                parent_set: None,
+                ..layer.clone()
+            }),
+            (Some(_), _) => {}
+        }
+    }
+
+    flattened
+}
+
+/// Whether `layer` is a [`LayerKind::Set`] that needs to be flattened before being blended with
+/// the layers below it.
+fn is_isolated(layer: &Layer) -> bool {
+    layer.kind == LayerKind::Set
+        && (layer.blending_mode != BlendingMode::PassThrough || layer.opacity < 100)
+}
+
+/// Same as [`composite`], but blends `layers` on top of `image`.
+fn composite_onto(
+    image: &mut [u8],
+    (width, height): (u32, u32),
+    layers: &[&Layer],
+    is_visible: &dyn Fn(&Layer) -> bool,
+) {
+    let sets = sets_by_id(layers.iter().copied());
+    // The outermost isolated set (found on `layers`) that contains `layer`.
+    let isolated_set = |layer: &Layer| {
+        ancestors(&sets, layer)
+            .filter(|set| is_isolated(set))
+            .last()
+            .map(|set| set.id)
+    };
+
+    // The last layer that is not `clipping`, for each of the sets.
+    let mut clipping_bases = HashMap::new();
+
+    for &layer in layers {
+        if matches!(layer.kind, LayerKind::_Unknown4 | LayerKind::_Unknown7) {
+            // Not known how to blend them, and they shouldn't become the base of a clipping group.
+            continue;
+        }
+
+        if isolated_set(layer).is_some() {
+            // Already blended alongside its set.
+            continue;
+        }
+
+        if layer.kind == LayerKind::Set && !is_isolated(layer) {
+            // The children of a `PassThrough` set are blended with the layers below it, so the
+            // first ones can also be clipped with them.
+            if let Some(&base) = clipping_bases.get(&layer.parent_set) {
+                clipping_bases.insert(Some(layer.id), base);
+            }
+        }
+
+        if !layer.clipping {
+            clipping_bases.insert(layer.parent_set, layer);
+        }
+
+        if layer.opacity == 0 || !is_visible(layer) {
+            continue;
+        }
+
+        if is_isolated(layer) {
+            let children: Vec<_> = layers
+                .iter()
+                .copied()
+                .filter(|child| isolated_set(child) == Some(layer.id))
+                .collect();
+
+            let mut group = vec![0; image.len()];
+            composite_onto(&mut group, (width, height), &children, is_visible);
+
+            // Places `data_origin` at the top-left corner of the canvas.
+            let group_layer = Layer {
+                bounds: LayerBounds {
+                    x: 8,
+                    y: 8,
+                    width,
+                    height,
+                },
+                ..layer.clone()
+            };
+            blend_onto(image, (width, height), &group_layer, &group, None);
+            continue;
+        }
+
+        let Some(ref data) = layer.data else {
+            continue;
+        };
+
+        let clipping_base = if layer.clipping {
+            match clipping_bases.get(&layer.parent_set) {
+                Some(base) if is_visible(base) => Some(*base),
+                // Nothing to clip with.
+                _ => continue,
+            }
+        } else {
+            None
+        };
+
+        blend_onto(image, (width, height), layer, data, clipping_base);
+    }
+}
+
+/// Gets the alpha of the pixel found at the `(x, y)` canvas coordinates of `layer`, with its
+/// `opacity` applied.
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+fn alpha_at(layer: &Layer, x: i32, y: i32) -> f32 {
+    let Some(ref data) = layer.data else {
+        return 0.0;
+    };
+
+    let (origin_x, origin_y) = layer.data_origin();
+    let (x, y) = (x - origin_x, y - origin_y);
+    if x < 0 || y < 0 || x >= layer.bounds.width as i32 || y >= layer.bounds.height as i32 {
+        return 0.0;
+    }
+
+    let idx = (y as usize * layer.bounds.width as usize + x as usize) * 4 + 3;
+    data[idx] as f32 / 255.0 * layer.opacity as f32 / 100.0
+}
+
+/// Blends the `data` of `layer` on top of `image`.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss
+)]
+fn blend_onto(
+    image: &mut [u8],
+    (width, height): (u32, u32),
+    layer: &Layer,
+    data: &[u8],
+    clipping_base: Option<&Layer>,
+) {
+    // `Binary` thresholds the alpha of the layer itself, before `opacity` is applied; the
+    // thresholded pixel is then drawn as if it was `Normal`.
+    let is_binary = layer.blending_mode == BlendingMode::Binary;
+    let blend = if is_binary {
+        (
+            blend_fn(BlendingMode::Normal),
+            alpha_fn(BlendingMode::Normal),
+        )
+    } else {
+        (blend_fn(layer.blending_mode), alpha_fn(layer.blending_mode))
+    };
+    let opacity = layer.opacity as f32 / 100.0;
+
+    let (origin_x, origin_y) = layer.data_origin();
+    let (layer_width, layer_height) = (layer.bounds.width as i32, layer.bounds.height as i32);
+
+    let (start_x, end_x) = (origin_x.max(0), (origin_x + layer_width).min(width as i32));
+    let (start_y, end_y) = (
+        origin_y.max(0),
+        (origin_y + layer_height).min(height as i32),
+    );
+
+    for y in start_y..end_y {
+        for x in start_x..end_x {
+            let src = ((y - origin_y) * layer_width + (x - origin_x)) as usize * 4;
+            let mut src: [u8; 4] = data[src..src + 4].try_into().expect("4 bytes");
+            if src[3] == 0 || (is_binary && src[3] < 128) {
+                continue;
+            }
+            if is_binary {
+                let [r, g, b] = [0, 1, 2].map(|c| pixel_ops::binary(0, src[c], src[3]));
+                src = [r, g, b, 255];
+            }
+
+            let scale = clipping_base.map_or(opacity, |base| opacity * alpha_at(base, x, y));
+            let fg = [0, 1, 2, 3].map(|c| (src[c] as f32 * scale).round() as u8);
+
+            let dst = (y as usize * width as usize + x as usize) * 4;
+            blend_pixel(&mut image[dst..dst + 4], fg, blend);
+        }
+    }
+}
+
+/// Blends the `fg` pixel on top of the `dst` one; both pre-multiplied alpha.
+pub(crate) fn blend_pixel(dst: &mut [u8], fg: [u8; 4], (blend, alpha): (BlendFn, AlphaFn)) {
+    let (bg_a, fg_a) = (dst[3], fg[3]);
+
+    for c in 0..3 {
+        dst[c] = blend(dst[c], fg[c], bg_a, fg_a);
+    }
+    dst[3] = alpha(bg_a, fg_a);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{internals::tests::resource, Sai};
+
+    const BYTES: &[u8] = resource!("layerfullred.sai");
+
+    fn layers() -> (u32, u32, Vec<Layer>) {
+        let sai = Sai::from(BYTES);
+        let canvas = sai.canvas().unwrap();
+
+        (canvas.width, canvas.height, sai.layers().unwrap())
+    }
+
+    #[test]
+    fn composite_works() {
+        let (width, height, layers) = layers();
+        let image = composite(width, height, &layers);
+
+        assert_eq!(image.len(), (width * height * 4) as usize);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn normal_is_over() {
+        // 64 + 200 * (1 - 128 / 255) = 163.6
+        assert_eq!(pixel_ops::normal(200, 64, 128), 164);
+        assert_eq!(pixel_ops::normal(200, 255, 255), 255);
+        assert_eq!(pixel_ops::normal(200, 0, 0), 200);
+    }
+
+    #[test]
+    fn opacity_is_applied_once() {
+        let (width, height, mut layers) = layers();
+        for layer in &mut layers {
+            layer.opacity = 50;
+        }
+        let image = composite(width, height, &layers);
+
+        // Pre-multiplied red at 50% alpha; applying the opacity twice would give 25%.
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [128, 0, 0, 128]));
+    }
+
+    #[test]
+    fn zero_opacity_is_skipped() {
+        let (width, height, layers) = layers();
+        let background = layers[0].clone();
+        let hidden = |blending_mode, data| Layer {
+            id: 100,
+            opacity: 0,
+            blending_mode,
+            data: Some(data),
+            ..background.clone()
+        };
+        let green: Vec<u8> = background
+            .data
+            .as_ref()
+            .unwrap()
+            .chunks_exact(4)
+            .flat_map(|_| [0, 255, 0, 255])
+            .collect();
+
+        for blending_mode in [
+            BlendingMode::Normal,
+            BlendingMode::Multiply,
+            BlendingMode::Screen,
+            BlendingMode::Overlay,
+            BlendingMode::Luminosity,
+            BlendingMode::Shade,
+            BlendingMode::LumiShade,
+            BlendingMode::Binary,
+        ] {
+            let layers = [background.clone(), hidden(blending_mode, green.clone())];
+            let image = composite(width, height, &layers);
+
+            assert!(
+                image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]),
+                "{blending_mode:?}"
+            );
+        }
+
+        // Skipped layers are not even read, so data that doesn't match the bounds is not a problem.
+        let layers = [background.clone(), hidden(BlendingMode::Normal, Vec::new())];
+        let image = composite(width, height, &layers);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn binary_opacity_is_applied_after_the_threshold() {
+        let (width, height, layers) = layers();
+        let binary = |opacity, alpha| Layer {
+            blending_mode: BlendingMode::Binary,
+            opacity,
+            data: layers[0].data.as_ref().map(|data| {
+                data.chunks_exact(4)
+                    .flat_map(|_| [alpha, 0, 0, alpha])
+                    .collect()
+            }),
+            ..layers[0].clone()
+        };
+
+        // Still drawn, as an opaque pixel with 40% opacity.
+        let image = composite(width, height, &[binary(40, 255)]);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [102, 0, 0, 102]));
+
+        let image = composite(width, height, &[binary(40, 200)]);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [102, 0, 0, 102]));
+
+        // Below the threshold, no matter the opacity.
+        let image = composite(width, height, &[binary(100, 100)]);
+        assert!(image.iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn set_opacity_is_applied() {
+        let (width, height, layers) = layers();
+        let set = Layer {
+            kind: LayerKind::Set,
+            id: 99,
+            opacity: 50,
+            data: None,
+            ..layers[0].clone()
+        };
+        let child = Layer {
+            parent_set: Some(set.id),
+            ..layers[0].clone()
+        };
+        let image = composite(width, height, &[set.clone(), child.clone()]);
+
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [128, 0, 0, 128]));
+
+        let set = Layer {
+            blending_mode: BlendingMode::PassThrough,
+            opacity: 100,
+            ..set
+        };
+        let image = composite(width, height, &[set, child]);
+
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn clipping_depends_on_set_isolation() {
+        let (width, height, layers) = layers();
+        let background = layers[0].clone();
+        let set = Layer {
+            kind: LayerKind::Set,
+            id: 99,
+            blending_mode: BlendingMode::PassThrough,
+            data: None,
+            ..background.clone()
+        };
+        let clipped = Layer {
+            id: 100,
+            clipping: true,
+            parent_set: Some(set.id),
+            data: background.data.as_ref().map(|data| {
+                data.chunks_exact(4)
+                    .flat_map(|_| [0, 255, 0, 255])
+                    .collect()
+            }),
+            ..background.clone()
+        };
+
+        // Clipped with the background, found below the set.
+        let image = composite(
+            width,
+            height,
+            &[background.clone(), set.clone(), clipped.clone()],
+        );
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]));
+
+        // Nothing to clip with inside of the set.
+        let set = Layer {
+            blending_mode: BlendingMode::Normal,
+            ..set
+        };
+        let image = composite(width, height, &[background, set, clipped]);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn pass_through_sets_blend_with_their_backdrop() {
+        let (width, height, layers) = layers();
+        let red = layers[0].clone();
+        let set = |id, blending_mode, parent_set| Layer {
+            kind: LayerKind::Set,
+            id,
+            blending_mode,
+            parent_set,
+            data: None,
+            ..red.clone()
+        };
+        let normal = set(99, BlendingMode::Normal, None);
+        let pass_through = set(100, BlendingMode::PassThrough, Some(normal.id));
+        let green = Layer {
+            id: 101,
+            blending_mode: BlendingMode::Multiply,
+            parent_set: Some(pass_through.id),
+            data: red.data.as_ref().map(|data| {
+                data.chunks_exact(4)
+                    .flat_map(|_| [0, 255, 0, 255])
+                    .collect()
+            }),
+            ..red.clone()
+        };
+
+        // Multiplied with the red layer below it.
+        let top_level = Layer {
+            parent_set: None,
+            ..pass_through.clone()
+        };
+        let image = composite(width, height, &[red.clone(), top_level, green.clone()]);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 0, 0, 255]));
+
+        // Multiplied with the (transparent) backdrop of the normal set instead.
+        let image = composite(
+            width,
+            height,
+            &[
+                red.clone(),
+                normal.clone(),
+                pass_through.clone(),
+                green.clone(),
+            ],
+        );
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]));
+
+        // Multiplied with the red layer inside of the normal set.
+        let inner_red = Layer {
+            id: 102,
+            parent_set: Some(normal.id),
+            ..red
+        };
+        let image = composite(width, height, &[normal, inner_red, pass_through, green]);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn cyclic_isolated_sets_dont_hang() {
+        let (width, height, layers) = layers();
+        let set = |id, parent_set| Layer {
+            kind: LayerKind::Set,
+            id,
+            parent_set: Some(parent_set),
+            data: None,
+            ..layers[0].clone()
+        };
+        let child = Layer {
+            parent_set: Some(1),
+            ..layers[0].clone()
+        };
+
+        // `1 -> 2 -> 1`; both of them are treated as top-level, so the child is still drawn.
+        let image = composite(width, height, &[set(1, 2), set(2, 1), child.clone()]);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+
+        // Same result, no matter the order of the sets.
+        let image = composite(width, height, &[set(2, 1), set(1, 2), child]);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn self_parented_sets_dont_hang() {
+        let (width, height, layers) = layers();
+        let set = Layer {
+            kind: LayerKind::Set,
+            id: 99,
+            parent_set: Some(99),
+            blending_mode: BlendingMode::PassThrough,
+            data: None,
+            ..layers[0].clone()
+        };
+        let child = Layer {
+            parent_set: Some(set.id),
+            ..layers[0].clone()
+        };
+
+        let layers = [set, child];
+        assert!(visibility(&layers)(&layers[1]));
+
+        let image = composite(width, height, &layers);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn flatten_groups_handles_cyclic_sets() {
+        let (width, height, layers) = layers();
+        let set = |id, parent_set| Layer {
+            kind: LayerKind::Set,
+            id,
+            parent_set: Some(parent_set),
+            data: None,
+            ..layers[0].clone()
+        };
+        let child = |parent_set| Layer {
+            id: 100 + parent_set,
+            parent_set: Some(parent_set),
+            ..layers[0].clone()
+        };
+
+        // `1` is its own parent, while `2` and `3` are each other's parent.
+        let flattened = flatten_groups(
+            width,
+            height,
+            &[set(1, 1), child(1), set(2, 3), set(3, 2), child(3)],
+        );
+
+        let ids: Vec<_> = flattened.iter().map(|layer| layer.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        for layer in &flattened {
+            assert_eq!(layer.kind, LayerKind::Regular);
+            assert_eq!(layer.parent_set, None);
+        }
+
+        let is_red = |layer: &Layer| {
+            layer
+                .data
+                .as_ref()
+                .unwrap()
+                .chunks_exact(4)
+                .all(|pixel| pixel == [255, 0, 0, 255])
+        };
+        assert!(is_red(&flattened[0]));
+        assert!(flattened[1].data.as_ref().unwrap().iter().all(|&c| c == 0));
+        assert!(is_red(&flattened[2]));
+    }
+
+    #[test]
+    fn unknown_kinds_are_skipped() {
+        let (width, height, layers) = layers();
+        let background = layers[0].clone();
+        let unknown = Layer {
+            kind: LayerKind::_Unknown4,
+            id: 99,
+            data: None,
+            ..background.clone()
+        };
+        let clipped = Layer {
+            id: 100,
+            clipping: true,
+            data: background.data.as_ref().map(|data| {
+                data.chunks_exact(4)
+                    .flat_map(|_| [0, 255, 0, 255])
+                    .collect()
+            }),
+            ..background.clone()
+        };
+
+        // Still clipped with the background.
+        let image = composite(width, height, &[background, unknown, clipped]);
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]));
+    }
+}
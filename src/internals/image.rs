@@ -1,22 +1,42 @@
-use png::{BitDepth, Encoder};
+#[cfg(feature = "png")]
+pub use png::{BitDepth, Compression, FilterType};
+
+#[cfg(feature = "png")]
+use png::{Decoder, Encoder, Transformations};
+#[cfg(feature = "png")]
+use std::io::Read;
 use std::{fs, io, path::Path};
 
 pub enum ColorType {
     Rgba,
-    #[allow(unused)]
     Grayscale,
 }
 
-/// New type to create 8bpc images.
+/// New type to create 8bpc images ( or 1bpc and 16bpc [`ColorType::Grayscale`] ones ).
+#[cfg(feature = "png")]
 pub struct PngImage {
     pub color: ColorType,
+    /// Either [`BitDepth::Eight`], or for `Grayscale` images:
+    ///
+    /// - [`BitDepth::One`], in which case any byte that is not `0` is saved as white.
+    /// - [`BitDepth::Sixteen`], in which case every pixel takes 2 bytes ( big endian ).
+    pub depth: BitDepth,
     pub width: u32,
     pub height: u32,
+    /// Trades encoding speed for a smaller file.
+    pub compression: Compression,
+    /// The filter applied to every row before compressing it.
+    pub filter: FilterType,
     // TODO(Unavailable): stride
 }
 
+#[cfg(feature = "png")]
 impl PngImage {
-    /// Saves bytes to the provided path.
+    /// Saves bytes ( one per channel, or two if 16bpc ) to the provided path.
+    ///
+    /// # Panics
+    ///
+    /// If `depth` is not `Eight`, or `One` or `Sixteen` for a `Grayscale` image.
     pub fn save<P>(self, bytes: &[u8], path: P) -> io::Result<()>
     where
         P: AsRef<Path>,
@@ -28,19 +48,253 @@ impl PngImage {
             ColorType::Rgba => png::ColorType::Rgba,
             ColorType::Grayscale => png::ColorType::Grayscale,
         });
-        encoder.set_depth(BitDepth::Eight);
+        encoder.set_depth(self.depth);
+        encoder.set_compression(self.compression);
+        encoder.set_filter(self.filter);
 
-        Ok(encoder.write_header()?.write_image_data(bytes)?)
+        match (self.color, self.depth) {
+            (_, BitDepth::Eight) | (ColorType::Grayscale, BitDepth::Sixteen) => {
+                Ok(encoder.write_header()?.write_image_data(bytes)?)
+            }
+            (ColorType::Grayscale, BitDepth::One) => {
+                // Each row starts on a new byte, with the leftmost pixel on the highest bit.
+                let packed: Vec<u8> = bytes
+                    .chunks(self.width as usize)
+                    .flat_map(|row| row.chunks(8))
+                    .map(|pixels| {
+                        pixels.iter().enumerate().fold(0, |byte, (idx, &pixel)| {
+                            byte | (u8::from(pixel != 0) << (7 - idx))
+                        })
+                    })
+                    .collect();
+                Ok(encoder.write_header()?.write_image_data(&packed)?)
+            }
+            _ => panic!("unsupported bit depth"),
+        }
     }
 }
 
+#[cfg(feature = "png")]
 impl Default for PngImage {
-    /// Creates `128x128` image with `Rgba` of ColorType.
+    /// Creates `128x128` image with `Rgba` of `ColorType` and `Eight` of `BitDepth`, using the
+    /// default `Compression` and `FilterType` of the encoder.
     fn default() -> Self {
         Self {
             color: ColorType::Rgba,
+            depth: BitDepth::Eight,
             width: 128,
             height: 128,
+            compression: Compression::default(),
+            filter: FilterType::default(),
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+/// Decodes a png image, returning its `width`, `height` and pixels in the RGBA color model (8bpc).
+pub fn decode_rgba(reader: impl Read) -> io::Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = Decoder::new(reader);
+    decoder.set_transformations(Transformations::normalize_to_color8() | Transformations::ALPHA);
+
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels)?;
+    pixels.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => pixels,
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        _ => return Err(io::ErrorKind::InvalidData.into()),
+    };
+
+    Ok((info.width, info.height, pixels))
+}
+
+/// New type to create 8bpc webp images, either lossless or lossy.
+#[cfg(feature = "webp")]
+pub struct WebpImage {
+    /// [`ColorType::Grayscale`] images are saved as opaque RGBA images, since webp doesn't have
+    /// a grayscale color type.
+    pub color: ColorType,
+    pub width: u32,
+    pub height: u32,
+    /// If `true` the pixels are kept as they are; otherwise, they are compressed lossy.
+    pub lossless: bool,
+    /// From `0.0` to `100.0`; for lossy images, trades a smaller file for a better looking image,
+    /// while for lossless ones, trades encoding speed for a smaller file.
+    pub quality: f32,
+}
+
+#[cfg(feature = "webp")]
+impl WebpImage {
+    /// Encodes bytes ( one per channel ) into a webp image.
+    ///
+    /// # Errors
+    ///
+    /// - If `quality` is not between `0.0` and `100.0` ([`io::ErrorKind::InvalidInput`]).
+    ///
+    /// - If it wasn't able to encode the image ( i.e: if `width` or `height` are `0` ).
+    ///
+    /// # Panics
+    ///
+    /// - If `bytes` has less than `width * height` pixels.
+    pub fn encode(&self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        if !(0.0..=100.0).contains(&self.quality) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "quality should be between 0 and 100",
+            ));
         }
+
+        let expanded;
+        let rgba = match self.color {
+            ColorType::Rgba => bytes,
+            ColorType::Grayscale => {
+                expanded = bytes
+                    .iter()
+                    .flat_map(|&value| [value, value, value, 255])
+                    .collect::<Vec<_>>();
+                &expanded
+            }
+        };
+
+        let webp = webp::Encoder::from_rgba(rgba, self.width, self.height)
+            .encode_simple(self.lossless, self.quality)
+            .map_err(|err| io::Error::other(format!("couldn't encode webp: {err:?}")))?;
+
+        Ok(webp.to_vec())
+    }
+
+    /// Saves bytes ( one per channel ) to the provided path; see [`WebpImage::encode`].
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`WebpImage::encode`].
+    ///
+    /// - If it wasn't able to save the image.
+    pub fn save<P>(self, bytes: &[u8], path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.encode(bytes)?)
+    }
+}
+
+#[cfg(feature = "webp")]
+impl Default for WebpImage {
+    /// Creates a lossless `128x128` image with `Rgba` of `ColorType`, using the default
+    /// `quality` of libwebp ( `75.0` ).
+    fn default() -> Self {
+        Self {
+            color: ColorType::Rgba,
+            width: 128,
+            height: 128,
+            lossless: true,
+            quality: 75.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "png")]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn compression_best_works() -> io::Result<()> {
+        let pixels: Vec<u8> = (0..16 * 16)
+            .flat_map(|idx| [idx as u8, 0, 255, 128])
+            .collect();
+
+        let path = std::env::temp_dir().join("saire-compression-best.png");
+        let png = PngImage {
+            width: 16,
+            height: 16,
+            compression: Compression::Best,
+            filter: FilterType::Paeth,
+            ..Default::default()
+        };
+        png.save(&pixels, &path)?;
+
+        let decoded = decode_rgba(fs::File::open(&path)?);
+        fs::remove_file(path)?;
+        assert_eq!(decoded?, (16, 16, pixels));
+
+        Ok(())
+    }
+
+    /// Decodes a webp image, returning its `width`, `height` and pixels in the RGBA color model.
+    #[cfg(feature = "webp")]
+    fn decode_webp(bytes: &[u8]) -> (u32, u32, Vec<u8>) {
+        let image = webp::Decoder::new(bytes)
+            .decode()
+            .expect("valid webp image");
+        let pixels = if image.is_alpha() {
+            image.to_vec()
+        } else {
+            image
+                .chunks_exact(3)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+                .collect()
+        };
+
+        (image.width(), image.height(), pixels)
+    }
+
+    #[test]
+    #[cfg(feature = "webp")]
+    #[allow(clippy::cast_possible_truncation)]
+    fn webp_round_trips() -> io::Result<()> {
+        // A gradient, with a transparent half.
+        let pixels: Vec<u8> = (0..32 * 32u32)
+            .flat_map(|idx| {
+                let (x, y) = ((idx % 32) as u8, (idx / 32) as u8);
+                [x * 8, y * 8, 128, if x < 16 { 255 } else { 0 }]
+            })
+            .collect();
+        let webp = WebpImage {
+            width: 32,
+            height: 32,
+            ..Default::default()
+        };
+
+        let assert_matches = |webp: &WebpImage, tolerance: u8| -> io::Result<()> {
+            let bytes = webp.encode(&pixels)?;
+            assert_eq!((&bytes[..4], &bytes[8..12]), (&b"RIFF"[..], &b"WEBP"[..]));
+
+            let (width, height, decoded) = decode_webp(&bytes);
+            assert_eq!((width, height), (32, 32));
+            for (expected, actual) in pixels.chunks_exact(4).zip(decoded.chunks_exact(4)) {
+                // The colors of fully transparent pixels are not kept ( even if lossless ).
+                let channels = if expected[3] == 0 { 3..4 } else { 0..4 };
+                for channel in channels {
+                    assert!(expected[channel].abs_diff(actual[channel]) <= tolerance);
+                }
+            }
+            Ok(())
+        };
+
+        assert_matches(&webp, 0)?;
+        let webp = WebpImage {
+            lossless: false,
+            quality: 90.0,
+            ..webp
+        };
+        assert_matches(&webp, 16)?;
+
+        let webp = WebpImage {
+            quality: 101.0,
+            ..webp
+        };
+        assert_eq!(
+            webp.encode(&pixels).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        Ok(())
     }
 }
@@ -1,6 +1,10 @@
 pub mod binreader;
-#[cfg(feature = "png")]
+pub mod compositor;
+#[cfg(any(feature = "png", feature = "webp"))]
 pub mod image;
+pub mod sync;
+#[cfg(feature = "tiff")]
+pub mod tiff;
 pub mod tree;
 
 #[cfg(test)]
@@ -15,13 +19,74 @@ pub mod tests {
     /// `toobig.sai` bytes.
     pub const SAMPLE: &[u8] = resource!("toobig.sai");
 
+    /// Gets a copy of `bytes`, where the entry of the root folder called `from` is renamed to
+    /// `to`; useful to simulate files that lack some entry.
+    pub fn rename_root_entry(bytes: &[u8], from: &str, to: &str) -> Vec<u8> {
+        use crate::cipher::{DataBlock, TableBlock, PAGE_SIZE};
+
+        const ROOT_INDEX: usize = 2;
+
+        let page = |bytes: &[u8], index: usize| -> [u8; PAGE_SIZE] {
+            bytes[index * PAGE_SIZE..][..PAGE_SIZE].try_into().unwrap()
+        };
+
+        let mut bytes = bytes.to_vec();
+        let mut table = TableBlock::decrypt(page(&bytes, 0), 0).unwrap();
+        let mut root =
+            DataBlock::decrypt(page(&bytes, ROOT_INDEX), table[ROOT_INDEX].checksum()).unwrap();
+
+        root.iter_mut()
+            .find(|entry| entry.name() == Some(from))
+            .expect("entry is on the root folder")
+            .set_name(to);
+        table[ROOT_INDEX].set_checksum(root.checksum());
+
+        bytes[ROOT_INDEX * PAGE_SIZE..][..PAGE_SIZE].copy_from_slice(&*root.encrypt(None));
+        bytes[..PAGE_SIZE].copy_from_slice(&*table.encrypt(0));
+        bytes
+    }
+
     #[allow(unused_imports)]
     pub(crate) use resource;
 }
 
 pub mod time {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
     /// Converts a `Windows FILETIME` timestamp to an `epoch` timestamp.
     pub const fn filetime_to_unixtime(filetime: u64) -> u64 {
         filetime / 10000000 - 11644473600
     }
+
+    /// Converts an `epoch` timestamp to a `Windows FILETIME` timestamp.
+    pub const fn unixtime_to_filetime(unixtime: u64) -> u64 {
+        (unixtime + 11644473600) * 10000000
+    }
+
+    /// The current time as a `Windows FILETIME` timestamp.
+    pub fn filetime_now() -> u64 {
+        let unixtime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        unixtime_to_filetime(unixtime)
+    }
+
+    /// Converts an `epoch` timestamp to a [`SystemTime`].
+    pub fn unixtime_to_system_time(unixtime: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(unixtime)
+    }
+
+    /// Converts an `epoch` timestamp to a [`chrono::DateTime`].
+    ///
+    /// # Panics
+    ///
+    /// If the timestamp is out of the range supported by `chrono` ( it can't be for the ones
+    /// that come from a `FILETIME` ).
+    #[cfg(feature = "chrono")]
+    pub fn unixtime_to_datetime(unixtime: u64) -> chrono::DateTime<chrono::Utc> {
+        i64::try_from(unixtime)
+            .ok()
+            .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+            .expect("timestamp is not out-of-bounds")
+    }
 }
@@ -0,0 +1,69 @@
+//! Interior mutability primitives, which are only thread-safe with the `parallel` feature.
+//!
+//! Without the feature, they are the `std::cell` types, so there is no overhead when a
+//! [`crate::Sai`] is only used from a single thread.
+
+#[cfg(not(feature = "parallel"))]
+use std::cell::{RefCell, RefMut};
+#[cfg(feature = "parallel")]
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// [`std::cell::OnceCell`], or [`std::sync::OnceLock`] with the `parallel` feature.
+#[cfg(not(feature = "parallel"))]
+pub type OnceCell<T> = std::cell::OnceCell<T>;
+/// [`std::cell::OnceCell`], or [`std::sync::OnceLock`] with the `parallel` feature.
+#[cfg(feature = "parallel")]
+pub type OnceCell<T> = std::sync::OnceLock<T>;
+
+/// The guard returned by [`Lock::lock`].
+#[cfg(not(feature = "parallel"))]
+pub type LockGuard<'a, T> = RefMut<'a, T>;
+/// The guard returned by [`Lock::lock`].
+#[cfg(feature = "parallel")]
+pub type LockGuard<'a, T> = MutexGuard<'a, T>;
+
+/// A [`RefCell`], or a [`Mutex`] with the `parallel` feature.
+#[derive(Debug, Default)]
+pub struct Lock<T> {
+    #[cfg(not(feature = "parallel"))]
+    inner: RefCell<T>,
+    #[cfg(feature = "parallel")]
+    inner: Mutex<T>,
+}
+
+impl<T> Lock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: value.into(),
+        }
+    }
+
+    /// Mutably borrows the value.
+    ///
+    /// # Panics
+    ///
+    /// Without the `parallel` feature, if the value is already borrowed; with it, this blocks
+    /// until the value is released by the other thread instead.
+    #[cfg(not(feature = "parallel"))]
+    pub fn lock(&self) -> LockGuard<'_, T> {
+        self.inner.borrow_mut()
+    }
+
+    /// Mutably borrows the value.
+    ///
+    /// # Panics
+    ///
+    /// Without the `parallel` feature, if the value is already borrowed; with it, this blocks
+    /// until the value is released by the other thread instead.
+    #[cfg(feature = "parallel")]
+    pub fn lock(&self) -> LockGuard<'_, T> {
+        // The values are caches, so they are still valid if a thread panicked while using them.
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> From<T> for Lock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
@@ -0,0 +1,130 @@
+use std::io::{self, Write};
+
+/// A page of a (baseline, uncompressed) tiff image.
+pub struct TiffPage<'a> {
+    pub width: u32,
+    pub height: u32,
+    /// Either `1` ( grayscale ) or `4` ( RGBA with straight alpha ).
+    pub samples: u16,
+    /// Saved as the `PageName` tag.
+    pub name: Option<&'a str>,
+    /// Saved as the `ImageDescription` tag.
+    pub description: Option<String>,
+    pub pixels: &'a [u8],
+}
+
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+/// Writes all `pages` as a single multi-page tiff image.
+///
+/// # Errors
+///
+/// - If the image ends up being bigger than 4 GiB ( tiff offsets are 32 bits ).
+///
+/// - If it wasn't able to write the image.
+#[allow(clippy::cast_possible_truncation)]
+pub fn write_tiff(writer: &mut impl Write, pages: &[TiffPage<'_>]) -> io::Result<()> {
+    // Little endian, 42, and the offset of the first IFD ( patched below ).
+    let mut bytes = b"II\x2A\x00\0\0\0\0".to_vec();
+    let mut next_ifd_offset = 4;
+
+    let offset = |len: usize| {
+        u32::try_from(len)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "tiff is bigger than 4 GiB"))
+    };
+    let shorts = |values: &[u16]| {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    };
+    let long = |value: u32| value.to_le_bytes().to_vec();
+    let ascii = |value: &str| [value.as_bytes(), b"\0"].concat();
+
+    for (number, page) in pages.iter().enumerate() {
+        let strip_offset = offset(bytes.len())?;
+        bytes.extend_from_slice(page.pixels);
+
+        let samples = usize::from(page.samples);
+        let is_rgba = page.samples == 4;
+        let page_number = u16::try_from(number).unwrap_or(u16::MAX);
+        let page_count = u16::try_from(pages.len()).unwrap_or(u16::MAX);
+
+        // ( tag, type, count, value ); sorted by tag, as the spec requires.
+        let mut entries: Vec<(u16, u16, usize, Vec<u8>)> = vec![
+            // NewSubfileType: a page of a multi-page image.
+            (254, LONG, 1, long(2)),
+            (256, LONG, 1, long(page.width)),
+            (257, LONG, 1, long(page.height)),
+            (258, SHORT, samples, shorts(&vec![8; samples])),
+            // Compression: none.
+            (259, SHORT, 1, shorts(&[1])),
+            // PhotometricInterpretation: either RGB or BlackIsZero.
+            (262, SHORT, 1, shorts(&[if is_rgba { 2 } else { 1 }])),
+        ];
+        if let Some(ref description) = page.description {
+            entries.push((270, ASCII, description.len() + 1, ascii(description)));
+        }
+        entries.extend([
+            (273, LONG, 1, long(strip_offset)),
+            (277, SHORT, 1, shorts(&[page.samples])),
+            (278, LONG, 1, long(page.height)),
+            (279, LONG, 1, long(offset(page.pixels.len())?)),
+            // X/YResolution: 72 dpi.
+            (282, RATIONAL, 1, [long(72), long(1)].concat()),
+            (283, RATIONAL, 1, [long(72), long(1)].concat()),
+            // PlanarConfiguration: chunky.
+            (284, SHORT, 1, shorts(&[1])),
+        ]);
+        if let Some(name) = page.name {
+            entries.push((285, ASCII, name.len() + 1, ascii(name)));
+        }
+        // ResolutionUnit: inch.
+        entries.push((296, SHORT, 1, shorts(&[2])));
+        entries.push((297, SHORT, 2, shorts(&[page_number, page_count])));
+        if is_rgba {
+            // ExtraSamples: unassociated ( straight ) alpha.
+            entries.push((338, SHORT, 1, shorts(&[2])));
+        }
+
+        // Values that don't fit on 4 bytes are stored outside of the IFD.
+        let values = entries
+            .iter()
+            .map(|(_, _, _, value)| {
+                if value.len() <= 4 {
+                    let mut inline = [0; 4];
+                    inline[..value.len()].copy_from_slice(value);
+                    Ok(inline)
+                } else {
+                    if !bytes.len().is_multiple_of(2) {
+                        bytes.push(0);
+                    }
+                    let value_offset = offset(bytes.len())?;
+                    bytes.extend_from_slice(value);
+                    Ok(value_offset.to_le_bytes())
+                }
+            })
+            .collect::<io::Result<Vec<[u8; 4]>>>()?;
+
+        if !bytes.len().is_multiple_of(2) {
+            bytes.push(0);
+        }
+        let ifd_offset = offset(bytes.len())?;
+        bytes[next_ifd_offset..][..4].copy_from_slice(&ifd_offset.to_le_bytes());
+
+        bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for ((tag, kind, count, _), value) in entries.iter().zip(values) {
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&kind.to_le_bytes());
+            bytes.extend_from_slice(&(*count as u32).to_le_bytes());
+            bytes.extend_from_slice(&value);
+        }
+        next_ifd_offset = bytes.len();
+        bytes.extend_from_slice(&[0; 4]);
+    }
+
+    writer.write_all(&bytes)
+}
@@ -87,11 +87,11 @@ impl LayerTree<'_> {
 
             if !parent_is_visible {
                 parent_name = Cow::Owned(parent_name.truecolor(100, 100, 100).italic().to_string());
-            };
+            }
 
             if parent_is_set {
                 parent_name = Cow::Owned(parent_name.truecolor(210, 210, 210).bold().to_string());
-            };
+            }
         };
 
         write!(f, "{prefix}")?;
@@ -146,7 +146,7 @@ impl LayerTree<'_> {
                     is_visible: *is_visible && parent_is_visible,
                 },
             )?;
-        };
+        }
 
         Ok(())
     }
@@ -156,7 +156,7 @@ impl Display for LayerTree<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         if let (true, false) = (f.alternate(), cfg!(feature = "colored")) {
             panic!("Activate the `colored` feature to enable colored output.")
-        };
+        }
 
         self.collect_root(f)
     }
@@ -12,26 +12,62 @@
 
 pub mod models;
 pub mod pixel_ops;
+pub mod validation;
 
 pub(crate) mod cipher;
 pub(crate) mod internals;
 pub(crate) mod vfs;
 
+pub use self::cipher::{ChecksumMismatchError, FatEntry, FatKind, TableEntry};
+
 use self::models::prelude::*;
-use crate::{cipher::FatEntry, internals::tree::LayerTree, vfs::*};
+use crate::{
+    internals::{binreader::BinReader, compositor, sync::OnceCell, tree::LayerTree},
+    pixel_ops::AlphaMode,
+    validation::{CanvasWarning, ValidationWarning},
+    vfs::{FatEntryReader, FileSystemReader, FsTraverser},
+};
+#[cfg(feature = "png")]
+use std::path::PathBuf;
 use std::{
-    fmt::{Display, Formatter},
-    fs::File,
-    io,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt::{Debug, Display, Formatter},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, Write},
     path::Path,
 };
 
+/// # Clone
+///
+/// Cloning a `Sai` is cheap when it was created from bytes, since the copies share them. When it
+/// was created from a path (see [`Sai::new_unchecked`]), the copy opens the file again, and it
+/// panics if the file can't be opened anymore.
+#[derive(Clone)]
 pub struct Sai {
     fs: FileSystemReader,
+    /// The [`Sai::thumbnail`], once it was read.
+    thumbnail: OnceCell<Thumbnail>,
+}
+
+/// Orderings supported by [`Sai::layers_sorted_by`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// From `lowest` to `highest`, as they are stacked on the image.
+    StackOrder,
+    /// By [`Layer::name`]; layers without a name come first.
+    Name,
+    /// By [`Layer::id`].
+    Id,
+    /// By the last time that the file of the layer was modified, oldest first.
+    ModifiedTime,
 }
 
 macro_rules! file_method {
     ($method_name:ident, $return_type:ty, $file_name:literal) => {
+        /// # Errors
+        ///
+        /// - If the file is not found, or if it can't be parsed.
         pub fn $method_name(&self) -> io::Result<$return_type> {
             let file = self.traverse_until($file_name)?;
             let mut reader = FatEntryReader::new(&self.fs, &file);
@@ -42,8 +78,11 @@ macro_rules! file_method {
 
 macro_rules! layers_method {
     ($method_name:ident, $layer_name:literal, $decompress_layer:literal) => {
+        /// # Errors
+        ///
+        /// - If the layers folder is not found, or if any of the layers can't be parsed.
         pub fn $method_name(&self) -> io::Result<Vec<Layer>> {
-            self.get_layers($layer_name, $decompress_layer)
+            self.get_layers($layer_name, $decompress_layer.then(DecodeLimits::default))
         }
     };
 }
@@ -51,7 +90,7 @@ macro_rules! layers_method {
 macro_rules! layers_no_decompress_method {
     ($method_name:ident, $layer_name:literal) => {
         fn $method_name(&self) -> io::Result<Vec<Layer>> {
-            self.get_layers($layer_name, false)
+            self.get_layers($layer_name, None)
         }
     };
 }
@@ -70,197 +109,2449 @@ impl Sai {
     ///
     /// - If the file is Corrupted/Invalid.
     pub fn new_unchecked(path: impl AsRef<Path>) -> Self {
+        Self::with_fs(FileSystemReader::open_unchecked(path))
+    }
+
+    /// Same as [`Sai::new_unchecked`], but meant for files that are too big to be kept on memory.
+    ///
+    /// # Memory
+    ///
+    /// Blocks are read (and decrypted) one at a time, through a buffer of 2 blocks ( 8 KiB ), and
+    /// only up to [`Sai::STREAMING_TABLE_CAPACITY`] table blocks ( 4 KiB each ) are cached at the
+    /// same time; [`Sai::new_unchecked`] caches all of them instead, which is faster when moving
+    /// back and forth between the start and the end of the file.
+    ///
+    /// Keep in mind that any [`Layer`] (or [`Thumbnail`]) that is read is still kept on memory
+    /// as a whole, and so are the blocks modified by [`Sai::edit_layers`].
+    ///
+    /// # Panics
+    ///
+    /// Same as [`Sai::new_unchecked`].
+    pub fn open_streaming(path: impl AsRef<Path>) -> Self {
+        Self::with_fs(FileSystemReader::open_bounded_unchecked(
+            path,
+            Self::STREAMING_TABLE_CAPACITY,
+        ))
+    }
+
+    /// Same as [`Sai::new_unchecked`], but maps the file to memory instead of reading it through
+    /// a buffer, so any block can be read (and decrypted) without seeking the file.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified (by this, or any other process) while the `Sai` (or any of
+    /// its clones) is alive; see [`memmap2::Mmap::map`].
+    ///
+    /// # Panics
+    ///
+    /// If the file could not be mapped; see also [`Sai::new_unchecked`].
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap(path: impl AsRef<Path>) -> Self {
+        // SAFETY: Ensured by the caller.
+        Self::with_fs(unsafe { FileSystemReader::open_mmap_unchecked(path) })
+    }
+
+    /// Same as [`Sai::from`], but decrypts the whole file up front, instead of decrypting each
+    /// block every time that it is read.
+    ///
+    /// This is faster when the same blocks are read more than once (e.g: calling [`Sai::layers`]
+    /// and [`Sai::thumbnail`] after [`Sai::validate`]), at the cost of keeping a second (decrypted)
+    /// copy of the file on memory; with the `parallel` feature, it also lets multiple threads read
+    /// blocks at the same time.
+    ///
+    /// # Panics
+    ///
+    /// If the file is Corrupted/Invalid.
+    pub fn new_in_memory(bytes: Vec<u8>) -> Self {
+        Self::with_fs(FileSystemReader::new_in_memory(bytes))
+    }
+
+    fn with_fs(fs: FileSystemReader) -> Self {
         Self {
-            fs: FileSystemReader::new_unchecked(File::open(path).unwrap()),
+            fs,
+            thumbnail: OnceCell::new(),
         }
     }
 
+    /// The maximum amount of table blocks that a [`Sai::open_streaming`] file caches.
+    pub const STREAMING_TABLE_CAPACITY: usize = 4;
+
+    /// The amount of bytes found after the last block of the file, which are ignored.
+    ///
+    /// Sai files are always made of whole blocks ( 4 KiB each ), so this is `0` unless some
+    /// other tool appended something at the end of the file ( e.g. a trailing newline ).
+    pub fn trailing_bytes(&self) -> usize {
+        self.fs.trailing_bytes()
+    }
+
     fn traverse_until(&self, filename: &str) -> io::Result<FatEntry> {
         self.fs
             .traverse_root(|_, entry| entry.name().is_some_and(|name| name.contains(filename)))
-            .ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("{filename} entry was not found"),
-                )
-            })
+            .ok_or_else(|| self.not_found(&format!("{filename} entry")))
+    }
+
+    fn get_layer_entry(&self, id: u32) -> io::Result<FatEntry> {
+        let filename = format!("{id:0>8x}");
+        self.fs
+            .traverse_root(|_, entry| entry.name().is_some_and(|name| name == filename))
+            .ok_or_else(|| self.not_found(&format!("layer {id}")))
+    }
+
+    /// The error returned when `what` is not found on the file system; it mentions if the root
+    /// folder is empty, since nothing can be found then.
+    fn not_found(&self, what: &str) -> io::Error {
+        let msg = if self.fs.root_is_unused() {
+            format!("{what} was not found; the root folder is empty")
+        } else {
+            format!("{what} was not found")
+        };
+
+        io::Error::new(io::ErrorKind::NotFound, msg)
+    }
+
+    fn get_layer(&self, id: u32, decompress_layer: bool) -> io::Result<Layer> {
+        let file = self.get_layer_entry(id)?;
+        let mut reader = FatEntryReader::new(&self.fs, &file);
+        Layer::from_reader(&mut reader, decompress_layer)
+            .map(|layer| with_fallback_name(layer, &file))
+            .map_err(|err| layer_error(&file, err))
+    }
+
+    /// Gets the [`Layer::bounds`] of the layer with the given `id`, without decompressing its data.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no layer with the given `id`, or if it can't be parsed.
+    pub fn layer_bounds(&self, id: u32) -> io::Result<LayerBounds> {
+        Ok(self.get_layer(id, false)?.bounds)
+    }
+
+    /// Gets the decoded data of the layer with the given `id`, along with the format of its
+    /// pixels.
+    ///
+    /// The data is returned as it is stored, so RGBA pixels always have
+    /// [`AlphaMode::Premultiplied`] alpha.
+    ///
+    /// # Errors
+    ///
+    /// - If the layer doesn't exist, or if its kind doesn't have pixel data.
+    pub fn layer_data(&self, id: u32) -> io::Result<LayerData> {
+        let layer = self.get_layer(id, true)?;
+        let Some(data) = layer.data else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("layer {id} doesn't have pixel data"),
+            ));
+        };
+        let (alpha_mode, channels) = if layer.kind == LayerKind::Mask {
+            (None, 1)
+        } else {
+            (Some(AlphaMode::Premultiplied), 4)
+        };
+
+        Ok(LayerData {
+            data,
+            width: layer.bounds.width,
+            height: layer.bounds.height,
+            alpha_mode,
+            channels,
+        })
+    }
+
+    /// Gets the [`TableEntry`] of every block of the file, ordered by their index.
+    ///
+    /// Every 512th entry (starting at `0`) belongs to a table block, the rest to data blocks; two
+    /// data blocks are part of the same file if they are chained by [`TableEntry::next_block`].
+    ///
+    /// # Errors
+    ///
+    /// - If the underlying file can't be read.
+    pub fn fat(&self) -> io::Result<Vec<TableEntry>> {
+        self.fs.table_entries()
+    }
+
+    /// Gets a checksum of the contents of the layer with the given `id`, without decrypting (nor
+    /// decompressing) them.
+    ///
+    /// The value is made from the checksums that the file system keeps for each of the blocks
+    /// that hold the layer file, so it can be used to cheaply detect if a layer changed between
+    /// two files.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no layer with the given `id`.
+    pub fn layer_data_checksum(&self, id: u32) -> io::Result<u32> {
+        let file = self.get_layer_entry(id)?;
+
+        Ok(self
+            .fs
+            .block_checksums(&file)
+            .into_iter()
+            .fold(0, |sum, checksum| sum.rotate_left(1) ^ checksum))
     }
 
+    /// Gets a hash of the canvas dimensions, and of the metadata and [`layer_data_checksum`] of
+    /// every layer, without decompressing any of them.
+    ///
+    /// The value changes whenever a layer changes, so it can be used as a cheap key to cache
+    /// things rendered out of the document. It is only stable between the same version of this
+    /// crate.
+    ///
+    /// [`layer_data_checksum`]: Sai::layer_data_checksum
+    ///
+    /// # Errors
+    ///
+    /// - If the canvas, or any of the layers, can't be read.
+    pub fn content_fingerprint(&self) -> io::Result<u64> {
+        let canvas = self.canvas()?;
+
+        let mut hasher = DefaultHasher::new();
+        (canvas.width, canvas.height).hash(&mut hasher);
+        for layer in self.layers_no_decompress()? {
+            layer.hash(&mut hasher);
+            self.layer_data_checksum(layer.id)?.hash(&mut hasher);
+        }
+
+        Ok(hasher.finish())
+    }
+
+    /// Gets every stream (`tag` and raw `body`) found after the header of the layer with the
+    /// given `id`, including the ones that are not parsed by [`Layer`].
+    ///
+    /// Mainly useful to reverse engineer fields that are not supported yet.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no layer with the given `id`, or if its streams are truncated.
+    pub fn raw_layer_stream(&self, id: u32) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let file = self.get_layer_entry(id)?;
+        let mut reader = BinReader::new(FatEntryReader::new(&self.fs, &file));
+        reader.skip(Layer::HEADER_SIZE)?;
+
+        let mut streams = Vec::new();
+        while let Some((tag, size)) = reader.read_stream_header::<[u8; 4]>().transpose()? {
+            // `[u8; 4]` to `[u8; 4]` never fails.
+            let tag = String::from_utf8_lossy(&tag.unwrap_or_default()).into_owned();
+            streams.push((tag, reader.read_stream_body(size)?.into_vec()));
+        }
+
+        Ok(streams)
+    }
+
+    /// Gets all the layers found on `layer_folder`, decompressing their data within `limits` (if
+    /// any).
     fn get_layers(
         &self,
         layer_folder: &'static str,
-        decompress_layers: bool,
+        limits: Option<DecodeLimits>,
     ) -> io::Result<Vec<Layer>> {
-        (0..)
-            .scan(
-                Some(self.traverse_until(layer_folder)?.next_block()),
-                |option, _| {
-                    option.map(|next_block| {
-                        let (folder, next) = self.fs.read_data(next_block as usize);
-                        *option = next;
-                        folder
-                    })
-                },
-            )
-            .flat_map(|folder| {
-                folder
-                    .iter()
-                    .filter(|i| i.flags() != 0)
-                    .map(|i| {
-                        let mut reader = FatEntryReader::new(&self.fs, i);
-                        Layer::from_reader(&mut reader, decompress_layers)
-                    })
-                    .collect::<Vec<_>>()
+        self.read_layers(&self.traverse_until(layer_folder)?, limits)
+    }
+
+    /// Same as [`Sai::get_layers`], but reads the layers of an already found `folder` entry.
+    fn read_layers(
+        &self,
+        folder: &FatEntry,
+        mut limits: Option<DecodeLimits>,
+    ) -> io::Result<Vec<Layer>> {
+        self.folder_entries(folder)
+            .iter()
+            .map(|entry| {
+                let mut reader = FatEntryReader::new(&self.fs, entry);
+                Layer::from_reader_limited(&mut reader, limits.as_mut())
+                    .map(|layer| with_fallback_name(layer, entry))
+                    .map_err(|err| layer_error(entry, err))
             })
             .collect()
     }
 
+    /// Iterates over every file and folder [`FatEntry`] of the underlying file system, depth-first;
+    /// the entries of a folder come right after the folder itself.
+    ///
+    /// Unlike [`Sai::canvas`], [`Sai::layers`], etc., this also yields the entries that this crate
+    /// doesn't know about ( e.g: the ones added by newer versions of sai ), which can be read
+    /// with [`FatEntry::next_block`] and [`FatEntry::size`].
+    ///
+    /// # Errors
+    ///
+    /// - If an entry doesn't have a valid [`FatEntry::kind`]; the iterator stops afterwards.
+    pub fn entries(&self) -> impl Iterator<Item = io::Result<FatEntry>> + '_ {
+        self.fs.entries()
+    }
+
+    /// Gets all the entries found directly inside of `folder`.
+    fn folder_entries(&self, folder: &FatEntry) -> Vec<FatEntry> {
+        let mut entries = Vec::new();
+        let mut next_index = Some(folder.next_block());
+        while let Some(index) = next_index {
+            let (data, next_block) = self.fs.read_data(index as usize);
+            next_index = next_block;
+
+            entries.extend(data.iter().filter(|entry| entry.flags() != 0).cloned());
+        }
+
+        entries
+    }
+
     file_method!(document, Document, ".");
     file_method!(canvas, Canvas, "canvas");
     file_method!(laytbl, LayerTable, "laytbl");
     file_method!(subtbl, LayerTable, "subtbl");
-    file_method!(thumbnail, Thumbnail, "thumbnail");
+
+    /// Gets the [`Thumbnail`] of the document.
+    ///
+    /// The thumbnail is only read (and decoded) the first time; later calls return a copy of it.
+    ///
+    /// # Errors
+    ///
+    /// - If the thumbnail is not found, or if it can't be decoded.
+    pub fn thumbnail(&self) -> io::Result<Thumbnail> {
+        if let Some(thumbnail) = self.thumbnail.get() {
+            return Ok(thumbnail.clone());
+        }
+
+        let file = self.traverse_until("thumbnail")?;
+        let thumbnail = Thumbnail::from_reader(&mut FatEntryReader::new(&self.fs, &file))?;
+        Ok(self.thumbnail.get_or_init(|| thumbnail).clone())
+    }
 
     layers_method!(layers, "layers", true);
     layers_method!(sublayers, "sublayers", true);
 
-    // This methods are private for the moment.
-    //
-    // TODO: Add the ability to re-parse the Layer to get the layer data at a later time.
+    /// Calls `f` with the id, the [`TileCoord`] and the pixels of every non-empty tile of the
+    /// [`Sai::layers`], as soon as each tile is decompressed.
+    ///
+    /// Tiles are [`TileCoord::SIZE`] by [`TileCoord::SIZE`] pre-multiplied RGBA pixels ( row by
+    /// row ), same as [`Layer::data`]; only [`LayerKind::Regular`] layers have tiles. Unlike
+    /// [`Sai::layers`], no whole layer is ever kept on memory, so the tiles can be directly
+    /// uploaded somewhere else ( e.g: to the textures of a GPU ).
+    ///
+    /// # Errors
+    ///
+    /// - If the layers folder is not found, or if any of the layers can't be parsed.
+    pub fn for_each_layer_tile<F>(&self, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(u32, TileCoord, &[u8]),
+    {
+        for entry in self.folder_entries(&self.traverse_until("layers")?) {
+            let mut reader = FatEntryReader::new(&self.fs, &entry);
+            Layer::from_reader_tiles(&mut reader, |layer, coord, tile| f(layer.id, coord, tile))
+                .map_err(|err| layer_error(&entry, err))?;
+        }
 
-    layers_no_decompress_method!(layers_no_decompress, "layers");
+        Ok(())
+    }
 
-    // TEST: I need a more "complicated" sample file.
-    //
-    // layers_no_decompress_method!(sublayers_no_decompress, "sublayers");
-}
+    /// Gets both the [`Canvas`] and the [`Sai::layers`], finding their entries with a single
+    /// traversal of the file system.
+    ///
+    /// # Errors
+    ///
+    /// - If the canvas or the layers are not found, or if any of them can't be parsed.
+    pub fn canvas_and_layers(&self) -> io::Result<(Canvas, Vec<Layer>)> {
+        let entries = RefCell::new((None, None));
+        self.fs.traverse_root(|_, entry| {
+            let mut entries = entries.borrow_mut();
+            match entry.name() {
+                Some("canvas") => entries.0 = Some(entry.to_owned()),
+                Some("layers") => entries.1 = Some(entry.to_owned()),
+                _ => {}
+            }
+            entries.0.is_some() && entries.1.is_some()
+        });
 
-impl From<&[u8]> for Sai {
-    fn from(bytes: &[u8]) -> Self {
-        Self { fs: bytes.into() }
+        let not_found = |filename| self.not_found(&format!("{filename} entry"));
+        let (canvas, layers) = entries.into_inner();
+        let (canvas, layers) = (
+            canvas.ok_or_else(|| not_found("canvas"))?,
+            layers.ok_or_else(|| not_found("layers"))?,
+        );
+
+        let mut reader = FatEntryReader::new(&self.fs, &canvas);
+        Ok((
+            Canvas::from_reader(&mut reader)?,
+            self.read_layers(&layers, Some(DecodeLimits::default()))?,
+        ))
     }
-}
 
-impl Display for Sai {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut layers = self.layers_no_decompress().unwrap();
-        self.laytbl().unwrap().sort_layers(&mut layers);
-        layers.reverse();
-        write!(f, "{}", LayerTree::new(layers))
+    /// Same as [`Sai::layers`], but fails if decompressing the layers goes over `limits`.
+    ///
+    /// Useful to not run out of memory when reading files that come from untrusted sources.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`Sai::layers`].
+    ///
+    /// - If decompressing the layers goes over `limits`.
+    pub fn layers_with_limits(&self, limits: DecodeLimits) -> io::Result<Vec<Layer>> {
+        self.get_layers("layers", Some(limits))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::internals::tests::SAMPLE as BYTES;
+    /// Calls `f` with every layer (without decompressing its data), writing back the properties
+    /// modified through [`LayerMut`].
+    ///
+    /// Only the blocks of the layers that were modified are re-encrypted; the changes are kept on
+    /// memory until they are written with [`Sai::write_to`].
+    ///
+    /// # Errors
+    ///
+    /// - If a new [`Layer::name`] doesn't fit on the layer file; the layers modified before are
+    ///   still written.
+    pub fn edit_layers(&mut self, mut f: impl FnMut(&mut LayerMut)) -> io::Result<()> {
+        let folder = self.traverse_until("layers")?;
+        for entry in self.folder_entries(&folder) {
+            let mut reader = FatEntryReader::new(&self.fs, &entry);
+            let mut layer = LayerMut::new(Layer::from_reader(&mut reader, false)?);
 
-    #[test]
-    fn author_works() -> io::Result<()> {
-        let sai = Sai::from(BYTES);
-        let document = sai.document()?;
+            f(&mut layer);
+            if !layer.is_modified() {
+                continue;
+            }
 
-        assert_eq!(document.id, 31);
-        assert_eq!(document.date_created, 1566984405);
-        assert_eq!(document.date_modified, 1567531929);
-        assert_eq!(document.machine_hash, 0x73851dcd1203b24d);
+            // NOTE: The header (and `name` stream) of a layer always fits on its first block.
+            let index = entry.next_block() as usize;
+            let mut page = *self.fs.read_data(index).0.into_virtual_page();
+            let len = (entry.size() as usize).min(page.len());
+            layer.layer().write_header(&mut page[..len])?;
+
+            self.fs.write_data(index, page.into());
+        }
 
         Ok(())
     }
 
-    const ID: u32 = 2;
+    /// Writes `bytes` as the contents of the file found at `path` (i.e: `/layers/00000002`) of the
+    /// underlying file system, creating the file if it doesn't exist yet.
+    ///
+    /// Like [`Sai::edit_layers`], the changes are kept on memory until they are written with
+    /// [`Sai::write_to`].
+    ///
+    /// # Errors
+    ///
+    /// - If any of the folders on `path` doesn't exist, or if `path` points to a folder.
+    /// - If the file name is longer than 31 bytes, or if `bytes` is bigger than 4 GiB.
+    pub fn write_file(&mut self, path: impl AsRef<Path>, bytes: &[u8]) -> io::Result<()> {
+        self.fs.write_file(path, bytes)?;
+        // The thumbnail could have been replaced.
+        self.thumbnail.take();
+        Ok(())
+    }
 
-    #[test]
-    fn laybtl_works() -> io::Result<()> {
-        let sai = Sai::from(BYTES);
-        let laytbl = sai.laytbl()?;
+    /// Creates an empty folder at `path` (i.e: `/sublayers`) of the underlying file system.
+    ///
+    /// Like [`Sai::edit_layers`], the changes are kept on memory until they are written with
+    /// [`Sai::write_to`].
+    ///
+    /// # Errors
+    ///
+    /// - If any of the folders on `path` (besides the last one) doesn't exist.
+    /// - If there is already an entry at `path`, or if the folder name is longer than 31 bytes.
+    pub fn create_dir(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.fs.create_dir(path)
+    }
 
-        assert_eq!(
-            laytbl[ID],
-            LayerRef {
-                id: ID,
-                kind: LayerKind::Regular,
-                tile_height: 78
-            }
-        );
-        assert_eq!(laytbl.get_index_of(ID), Some(0));
-        assert_eq!(laytbl.into_iter().count(), 1);
+    /// Writes the `.sai` file, including the changes made by [`Sai::edit_layers`],
+    /// [`Sai::write_file`] and [`Sai::create_dir`].
+    ///
+    /// # Errors
+    ///
+    /// - If it wasn't able to write to `writer`.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        self.fs.write_to(&mut writer)
+    }
 
-        Ok(())
+    /// Same as [`Sai::layers`], but consumes `self`, so the underlying file (or buffer) is released
+    /// as soon as the layers are read.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`Sai::layers`].
+    pub fn into_layers(self) -> io::Result<Vec<Layer>> {
+        self.layers()
     }
 
-    #[test]
-    fn layers_works() -> io::Result<()> {
-        let sai = Sai::from(BYTES);
-        let layers = sai.layers_no_decompress()?;
+    /// Gets the layer marked as the [`Canvas::selection_source`].
+    ///
+    /// Returns [`None`] if there is no selection source, or if it points to a layer that doesn't
+    /// exist anymore.
+    ///
+    /// # Errors
+    ///
+    /// - If the canvas, or the selection source layer, can't be read.
+    pub fn selection_source(&self) -> io::Result<Option<Layer>> {
+        self.find_layer(self.canvas()?.selection_source)
+    }
 
-        assert_eq!(layers.len(), 1);
+    fn find_layer(&self, id: Option<u32>) -> io::Result<Option<Layer>> {
+        let Some(id) = id else {
+            return Ok(None);
+        };
 
-        let layer = &layers[0];
+        match self.get_layer(id, true) {
+            Ok(layer) => Ok(Some(layer)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
 
-        assert_eq!(layer.kind, LayerKind::Regular);
-        assert_eq!(layer.id, ID);
-        assert_eq!(layer.bounds.x, -125);
-        assert_eq!(layer.bounds.y, -125);
-        assert_eq!(layer.bounds.width, 2464);
-        assert_eq!(layer.bounds.height, 2496);
-        assert_eq!(layer.opacity, 100);
-        assert_eq!(layer.visible, true);
-        assert_eq!(layer.preserve_opacity, false);
-        assert_eq!(layer.clipping, false);
-        assert_eq!(layer.blending_mode, BlendingMode::Normal);
-        assert_eq!(layer.name, Some("Layer1".into()));
-        assert_eq!(layer.parent_set, None);
-        assert_eq!(layer.parent_layer, None);
-        assert_eq!(layer.open, None);
-        assert_eq!(layer.texture, None);
-        assert_eq!(layer.effect, None);
-        // FIX(Unavailable): layers_no_decompress
-        assert_eq!(layer.data, None);
+    /// Gets a small preview of the layer with the specified `id`.
+    ///
+    /// The layer data is cropped to its [`content_bounds`], and then downscaled (keeping its aspect
+    /// ratio) until neither of its sides is bigger than `max_dim`. The returned tuple holds the
+    /// `(width, height, pixels)` of the preview, with the pixels in the RGBA `straight` color
+    /// format.
+    ///
+    /// If all the pixels of the layer are transparent, an empty preview is returned.
+    ///
+    /// # Errors
+    ///
+    /// - If the layer doesn't exist, or if its kind doesn't have pixel data.
+    ///
+    /// [`content_bounds`]: Layer::content_bounds
+    pub fn layer_thumbnail(&self, id: u32, max_dim: u32) -> io::Result<(u32, u32, Vec<u8>)> {
+        let layer = self.get_layer(id, true)?;
+        if layer.data.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("layer {id} doesn't have pixel data"),
+            ));
+        }
+        let Some((content, cropped)) = layer.cropped_content() else {
+            return Ok((0, 0, Vec::new()));
+        };
+        let (width, height) = (content.width, content.height);
 
-        Ok(())
+        let (new_width, new_height) = fit_within((width, height), max_dim);
+        let scaled = pixel_ops::downscale_rgba(&cropped, (width, height), (new_width, new_height));
+
+        Ok((
+            new_width,
+            new_height,
+            pixel_ops::premultiplied_to_straight(&scaled),
+        ))
     }
 
-    #[test]
-    fn canvas_works() -> io::Result<()> {
-        let sai = Sai::from(BYTES);
-        let canvas = sai.canvas()?;
+    /// Flattens all the layers (ordered with the [`LayerTable`]) into a canvas sized image.
+    ///
+    /// The children of each [`LayerKind::Set`] are flattened on their own, and the result is
+    /// blended with the `blending_mode` and `opacity` of the set; only the children of a
+    /// [`BlendingMode::PassThrough`] set (with an `opacity` of 100) are blended directly with the
+    /// layers below it. Layers inside of a set that is not visible are not visible either, and
+    /// `clipping` layers are only clipped with layers of the same set.
+    ///
+    /// For now, `textures`, `effects` and `masks` are not taken into account.
+    ///
+    /// # Errors
+    ///
+    /// - If the canvas, the layer table, or any of the layers can't be read.
+    pub fn composite(&self) -> io::Result<CompositeImage> {
+        let canvas = self.canvas()?;
+        let layers = self.sorted_layers()?;
 
-        assert_eq!(canvas.alignment, 16);
-        assert_eq!(canvas.width, 2250);
-        assert_eq!(canvas.height, 2250);
-        assert_eq!(canvas.dots_per_inch, Some(72.0));
-        assert_eq!(canvas.size_unit, Some(SizeUnit::Pixels));
-        assert_eq!(canvas.resolution_unit, Some(ResolutionUnit::PixelsInch));
-        assert_eq!(canvas.selection_source, None);
-        assert_eq!(canvas.selected_layer, Some(2));
+        let mut data = compositor::composite(canvas.width, canvas.height, &layers);
+        pixel_ops::premultiplied_to_straight_in_place(&mut data);
 
-        Ok(())
+        Ok(CompositeImage {
+            width: canvas.width,
+            height: canvas.height,
+            data,
+        })
     }
 
-    #[test]
-    fn subtbl_is_err() {
-        let sai = Sai::from(BYTES);
-        assert!(sai.subtbl().is_err());
+    /// Whether or not the flattened image of this sai file has any pixel that is not fully opaque.
+    ///
+    /// Useful to decide if an export needs an alpha channel or not.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`Sai::composite`].
+    pub fn flattened_has_transparency(&self) -> io::Result<bool> {
+        Ok(self.flatten()?.chunks_exact(4).any(|pixel| pixel[3] < 255))
     }
 
-    #[test]
-    fn sublayers_is_err() -> io::Result<()> {
-        let sai = Sai::from(BYTES);
-        assert!(sai.sublayers().is_err());
+    /// Gets all the layers, with each top-level [`LayerKind::Set`] (and everything inside of it)
+    /// replaced by a single [`LayerKind::Regular`] layer holding the rendered result.
+    ///
+    /// The replacement keeps the `id`, `name`, `opacity`, `visible` and `clipping` properties of
+    /// the set, and its data covers the whole canvas. Layers are returned ordered from `lowest`
+    /// to `highest`.
+    ///
+    /// NOTE: [`BlendingMode::PassThrough`] sets are turned into [`BlendingMode::Normal`] layers,
+    /// so their children will no longer blend with the layers below them.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`Sai::composite`].
+    pub fn flatten_groups(&self) -> io::Result<Vec<Layer>> {
+        let canvas = self.canvas()?;
+        let layers = self.sorted_layers()?;
 
-        Ok(())
+        Ok(compositor::flatten_groups(
+            canvas.width,
+            canvas.height,
+            &layers,
+        ))
     }
 
-    #[test]
-    fn thumbnail_works() -> io::Result<()> {
-        let sai = Sai::from(BYTES);
-        let thumbnail = sai.thumbnail()?;
-
-        assert_eq!(thumbnail.width, 140);
+    /// Packs every visible [`LayerKind::Regular`] layer (cropped to its [`content_bounds`]) into a
+    /// single png atlas saved at `path`.
+    ///
+    /// Layers which pixels are all transparent are not included.
+    ///
+    /// # Errors
+    ///
+    /// - If there are no layers to pack.
+    ///
+    /// - If it wasn't able to save the image.
+    ///
+    /// [`content_bounds`]: Layer::content_bounds
+    #[cfg(feature = "png")]
+    pub fn export_spritesheet(&self, path: impl AsRef<Path>) -> io::Result<AtlasMeta> {
+        use crate::internals::image::PngImage;
+
+        let layers = self.sorted_layers()?;
+        let is_visible = compositor::visibility(&layers);
+
+        let contents: Vec<_> = layers
+            .iter()
+            .filter(|layer| layer.kind == LayerKind::Regular && is_visible(layer))
+            .filter_map(|layer| layer.cropped_content().map(|content| (layer, content)))
+            .collect();
+        if contents.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "there are no layers to pack",
+            ));
+        }
+
+        let meta = AtlasMeta::pack(contents.iter().map(|(layer, (bounds, _))| {
+            (layer.id, layer.name.clone(), bounds.width, bounds.height)
+        }));
+
+        let stride = meta.width as usize * 4;
+        let mut atlas = vec![0; stride * meta.height as usize];
+        for (sprite, (_, (_, pixels))) in meta.sprites.iter().zip(&contents) {
+            let row_len = sprite.width as usize * 4;
+            for (row, src) in pixels.chunks_exact(row_len).enumerate() {
+                let dst = (sprite.y as usize + row) * stride + sprite.x as usize * 4;
+                atlas[dst..dst + row_len].copy_from_slice(src);
+            }
+        }
+
+        let png = PngImage {
+            width: meta.width,
+            height: meta.height,
+            ..Default::default()
+        };
+        png.save(&pixel_ops::premultiplied_to_straight(&atlas), path)?;
+
+        Ok(meta)
+    }
+
+    /// Saves the document as a multi-page tiff image at `path`.
+    ///
+    /// The first page is the [`Sai::composite`] ( with the dimensions of the [`Canvas`] ), and
+    /// the next ones are every [`LayerKind::Regular`] ( as RGBA ) and [`LayerKind::Mask`] ( as
+    /// grayscale ) layer, from the bottom to the top; masks come after every other layer. Each of
+    /// them is tagged with its [`Layer::name`], and its position on the canvas (
+    /// [`Layer::data_origin`] ) and size as the description ( `x,y,width,height` ).
+    ///
+    /// Layers are saved at the full size of their bounds, even if some of them ends up off-canvas
+    /// ( they are not cropped ).
+    ///
+    /// # Errors
+    ///
+    /// - If the image ends up being bigger than 4 GiB ([`io::ErrorKind::InvalidInput`]).
+    ///
+    /// - If it wasn't able to save the image.
+    #[cfg(feature = "tiff")]
+    pub fn to_tiff(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        use crate::internals::tiff::{write_tiff, TiffPage};
+
+        let composite = self.composite()?;
+        let mut layers = self.sorted_layers()?;
+        match self.sublayers() {
+            Ok(sublayers) => layers.extend(sublayers),
+            // `sublayers` only exists if there is at least one mask.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        let layers: Vec<_> = layers
+            .iter()
+            .filter_map(|layer| {
+                let data = layer.data.as_ref()?;
+                let (samples, pixels) = if layer.kind == LayerKind::Mask {
+                    (1, data.clone())
+                } else {
+                    (4, pixel_ops::premultiplied_to_straight(data))
+                };
+                Some((layer, samples, pixels))
+            })
+            .collect();
+
+        let mut pages = vec![TiffPage {
+            width: composite.width,
+            height: composite.height,
+            samples: 4,
+            name: None,
+            description: None,
+            pixels: &composite.data,
+        }];
+        pages.extend(layers.iter().map(|(layer, samples, pixels)| {
+            let (x, y) = layer.data_origin();
+            let LayerBounds { width, height, .. } = layer.bounds;
+            TiffPage {
+                width,
+                height,
+                samples: *samples,
+                name: layer.name.as_deref(),
+                description: Some(format!("{x},{y},{width},{height}")),
+                pixels,
+            }
+        }));
+
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        write_tiff(&mut file, &pages)?;
+        file.flush()
+    }
+
+    /// Saves the [`Sai::composite`] as a webp image at `path`; see [`CompositeImage::to_webp`].
+    ///
+    /// # Errors
+    ///
+    /// - If `quality` is not between `0.0` and `100.0` ([`io::ErrorKind::InvalidInput`]).
+    ///
+    /// - If it wasn't able to encode, or save the image.
+    #[cfg(feature = "webp")]
+    pub fn to_webp<P>(&self, path: P, quality: Option<f32>) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.composite()?.to_webp(path, quality)
+    }
+
+    /// Same as [`Layer::to_png`] for the [`LayerKind::Mask`] with the given `id`, but saves it as a
+    /// 16-bit grayscale image.
+    ///
+    /// SAI stores mask pixels as values from `0` to `0x4000`, which [`Layer::data`] scales down
+    /// to 8 bits (losing the difference between close values); here they are scaled up to 16
+    /// bits instead, so no value is lost.
+    ///
+    /// # Errors
+    ///
+    /// - If the layer was not found, or if it is not a [`LayerKind::Mask`]
+    ///   ([`io::ErrorKind::InvalidInput`]).
+    ///
+    /// - If it wasn't able to save the image.
+    #[cfg(feature = "png")]
+    pub fn mask_to_png16<P>(&self, id: u32, path: Option<P>) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        use crate::internals::image::{BitDepth, ColorType, PngImage};
+
+        let file = self.get_layer_entry(id)?;
+        let mut reader = FatEntryReader::new(&self.fs, &file);
+        let (layer, pixels) = Layer::mask_from_reader16(&mut reader)
+            .map_err(|err| layer_error(&file, err))?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("layer {id} is not a mask"),
+                )
+            })?;
+
+        let png = PngImage {
+            color: ColorType::Grayscale,
+            depth: BitDepth::Sixteen,
+            width: layer.bounds.width,
+            height: layer.bounds.height,
+            ..Default::default()
+        };
+        let bytes: Vec<u8> = pixels
+            .iter()
+            .flat_map(|pixel| pixel.to_be_bytes())
+            .collect();
+        png.save(&bytes, layer.image_path(path, "png"))
+    }
+
+    /// Saves all the [`LayerKind::Regular`] layers as png files inside of `dir` (named after their
+    /// [`Layer::id`]), decompressing and encoding them on multiple threads.
+    ///
+    /// Returns the paths of the created files, on the same order as [`Sai::layers`].
+    ///
+    /// # Errors
+    ///
+    /// - If it wasn't able to save any of the images.
+    #[cfg(feature = "png")]
+    pub fn export_layers_par(&self, dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+        use std::{io::Read, num::NonZeroUsize, panic, thread};
+
+        // `FileSystemReader` can't be shared between threads, so all the layer files are read
+        // (and decrypted) upfront; only decompressing them is done on parallel.
+        let files = self
+            .folder_entries(&self.traverse_until("layers")?)
+            .iter()
+            .map(|entry| {
+                let mut bytes = vec![0; entry.size() as usize];
+                FatEntryReader::new(&self.fs, entry).read_exact(&mut bytes)?;
+                Ok(bytes)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let export = |bytes: &[u8], dir: &Path| -> io::Result<Option<PathBuf>> {
+            let layer = Layer::from_reader(&mut &*bytes, true)?;
+            if layer.data.is_none() {
+                return Ok(None);
+            }
+
+            let path = dir.join(format!("{:0>8x}.png", layer.id));
+            layer.to_png(Some(&path))?;
+            Ok(Some(path))
+        };
+
+        let dir = dir.as_ref();
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let chunk_size = files.len().div_ceil(threads).max(1);
+        thread::scope(|scope| {
+            let handles: Vec<_> = files
+                .chunks(chunk_size)
+                .map(|files| {
+                    scope.spawn(move || {
+                        files
+                            .iter()
+                            .filter_map(|bytes| export(bytes, dir).transpose())
+                            .collect::<io::Result<Vec<_>>>()
+                    })
+                })
+                .collect();
+
+            let mut paths = Vec::with_capacity(files.len());
+            for handle in handles {
+                paths.extend(
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| panic::resume_unwind(err))?,
+                );
+            }
+
+            Ok(paths)
+        })
+    }
+
+    /// Checks that the different files of this document are consistent with each other.
+    ///
+    /// An empty list means that nothing wrong was found; see [`ValidationWarning`] for the
+    /// checks that are done.
+    ///
+    /// # Errors
+    ///
+    /// - If any of the files that are checked can't be read.
+    pub fn validate(&self) -> io::Result<Vec<ValidationWarning>> {
+        let canvas = self.canvas()?;
+        let Some(laytbl) = self.optional_laytbl()? else {
+            return Ok(vec![ValidationWarning::MissingLayerTable]);
+        };
+
+        let subtbl = match self.subtbl() {
+            Ok(subtbl) => Some(subtbl),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        let sublayers = match self.sublayers_no_decompress() {
+            Ok(sublayers) => sublayers,
+            // `sublayers` only exists if there is at least one mask.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(validation::selected_layer(&canvas, &laytbl)
+            .into_iter()
+            .chain(validation::masks(subtbl.as_ref(), &sublayers))
+            .collect())
+    }
+
+    /// Decrypts every block of the file, checking that their checksums match the ones that the
+    /// file system keeps for them.
+    ///
+    /// Unlike reading the file, this doesn't panic on the first block that doesn't match;
+    /// instead, the index of every block that doesn't match is returned along with its error,
+    /// so the file can be checked before trusting [`Sai::new_unchecked`] with it.
+    ///
+    /// The blocks that belong to a table block that doesn't match can't be checked, so only the
+    /// table block itself is reported for them.
+    ///
+    /// # Errors
+    ///
+    /// - The index and error of every block whose checksum doesn't match.
+    pub fn verify_integrity(&self) -> Result<(), Vec<(usize, ChecksumMismatchError)>> {
+        let mismatches = self.fs.verify_blocks();
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    /// Flags [`Canvas`] metadata that doesn't look right, like zero dimensions or a thumbnail that
+    /// doesn't have the aspect ratio of the canvas.
+    ///
+    /// Unlike [`Sai::validate`], the files that can't be read are not reported as errors; their
+    /// checks are skipped instead.
+    pub fn suggest_canvas_fixes(&self) -> Vec<CanvasWarning> {
+        let Ok(canvas) = self.canvas() else {
+            return Vec::new();
+        };
+
+        validation::canvas_fixes(
+            &canvas,
+            self.laytbl().ok().as_ref(),
+            self.thumbnail().ok().as_ref(),
+        )
+    }
+
+    /// Counts how many layers (and masks) of each [`LayerKind`] there are.
+    ///
+    /// # Errors
+    ///
+    /// - If the layers ( or sublayers ) can't be read.
+    pub fn layer_stats(&self) -> io::Result<BTreeMap<LayerKind, usize>> {
+        let mut layers = self.layers_no_decompress()?;
+        match self.sublayers_no_decompress() {
+            Ok(sublayers) => layers.extend(sublayers),
+            // `sublayers` only exists if there is at least one mask.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        let mut stats = BTreeMap::new();
+        for layer in layers {
+            *stats.entry(layer.kind).or_default() += 1;
+        }
+
+        Ok(stats)
+    }
+
+    /// Maps the id of every [`LayerKind::Set`] to its number of direct children ( the layers whose
+    /// `parent_set` is that set ).
+    ///
+    /// # Errors
+    ///
+    /// - If the layers can't be read.
+    pub fn set_child_counts(&self) -> io::Result<HashMap<u32, usize>> {
+        let layers = self.layers_no_decompress()?;
+
+        let mut counts: HashMap<_, _> = layers
+            .iter()
+            .filter(|layer| layer.kind == LayerKind::Set)
+            .map(|layer| (layer.id, 0))
+            .collect();
+        for parent in layers.iter().filter_map(|layer| layer.parent_set) {
+            if let Some(count) = counts.get_mut(&parent) {
+                *count += 1;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// Maps the id of every layer that has a [`LayerKind::Mask`] to the id of its mask, without
+    /// decompressing any of them.
+    ///
+    /// The layers themselves don't know about their masks ( only masks store their
+    /// [`Layer::parent_layer`] ), so use this to know if a layer has a mask, e.g:
+    /// `masks.contains_key(&layer.id)`.
+    ///
+    /// # Errors
+    ///
+    /// - If the layers ( or sublayers ) can't be read.
+    pub fn masks_by_layer(&self) -> io::Result<HashMap<u32, u32>> {
+        let sublayers = match self.sublayers_no_decompress() {
+            Ok(sublayers) => sublayers,
+            // `sublayers` only exists if there is at least one mask.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => return Err(err),
+        };
+
+        Ok(sublayers
+            .into_iter()
+            .filter(|layer| layer.kind == LayerKind::Mask)
+            .filter_map(|mask| Some((mask.parent_layer?, mask.id)))
+            .collect())
+    }
+
+    /// Whether or not the document was modified after `unix_secs` ( an epoch timestamp ), based
+    /// on [`Document::date_modified`].
+    ///
+    /// Useful to only re-export the files that changed since the last time they were exported.
+    ///
+    /// # Errors
+    ///
+    /// - If the document file can't be read.
+    pub fn modified_since(&self, unix_secs: u64) -> io::Result<bool> {
+        Ok(self.document()?.date_modified > unix_secs)
+    }
+
+    /// Whether or not both documents have the same [`Canvas`] and [`Document`] metadata.
+    ///
+    /// The layers are not compared; see [`Sai::content_fingerprint`] for that.
+    ///
+    /// # Errors
+    ///
+    /// - If the canvas, or the document file, of either `Sai` can't be read.
+    pub fn metadata_equals(&self, other: &Sai) -> io::Result<bool> {
+        Ok(self.canvas()? == other.canvas()? && self.document()? == other.document()?)
+    }
+
+    /// Whether or not the stored [`Thumbnail`] still looks like the layers of this document.
+    ///
+    /// The flattened image is downscaled to the size of the thumbnail, and then the mean
+    /// difference between both ( with every channel normalized to `0.0..=1.0` ) is compared
+    /// against `tolerance`. A thumbnail that is bigger than the canvas is never current.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`Sai::thumbnail`] and [`Sai::composite`].
+    ///
+    /// - If the thumbnail doesn't have `width * height` pixels ([`io::ErrorKind::InvalidData`]).
+    #[allow(clippy::cast_precision_loss)]
+    pub fn thumbnail_is_current(&self, tolerance: f32) -> io::Result<bool> {
+        let canvas = self.canvas()?;
+        let thumbnail = self.thumbnail()?;
+
+        if thumbnail.pixels.len() != thumbnail.width as usize * thumbnail.height as usize * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "thumbnail pixels don't match its size",
+            ));
+        }
+        if thumbnail.width > canvas.width || thumbnail.height > canvas.height {
+            return Ok(false);
+        }
+
+        let mut flattened = pixel_ops::downscale_rgba(
+            &self.flatten()?,
+            (canvas.width, canvas.height),
+            (thumbnail.width, thumbnail.height),
+        );
+        // NOTE(rev-eng): Thumbnails are always opaque, because SAI draws them on top of a white
+        // background.
+        for pixel in flattened.chunks_exact_mut(4) {
+            let transparency = 255 - pixel[3];
+            // Channels bigger than the alpha are not valid pre-multiplied colors, but they can
+            // still be found on malformed layers.
+            pixel[..3]
+                .iter_mut()
+                .for_each(|c| *c = c.saturating_add(transparency));
+            pixel[3] = 255;
+        }
+
+        let difference: u64 = flattened
+            .iter()
+            .zip(&thumbnail.pixels)
+            .map(|(&a, &b)| u64::from(a.abs_diff(b)))
+            .sum();
+        let mean = difference as f64 / 255.0 / thumbnail.pixels.len().max(1) as f64;
+
+        Ok(mean <= f64::from(tolerance))
+    }
+
+    /// Gets all the layers, ordered by `key`.
+    ///
+    /// Layers that compare equal keep their stacking order.
+    ///
+    /// # Errors
+    ///
+    /// - Same as [`Sai::layers`].
+    pub fn layers_sorted_by(&self, key: SortKey) -> io::Result<Vec<Layer>> {
+        let mut layers = self.sorted_layers()?;
+        match key {
+            SortKey::StackOrder => {}
+            SortKey::Name => layers.sort_by(|a, b| a.name.cmp(&b.name)),
+            SortKey::Id => layers.sort_by_key(|layer| layer.id),
+            SortKey::ModifiedTime => {
+                let times = layers
+                    .iter()
+                    .map(|layer| Ok((layer.id, self.get_layer_entry(layer.id)?.filetime())))
+                    .collect::<io::Result<HashMap<_, _>>>()?;
+                layers.sort_by_key(|layer| times[&layer.id]);
+            }
+        }
+
+        Ok(layers)
+    }
+
+    /// Gets all the layers, ordered from `lowest` to `highest`.
+    fn sorted_layers(&self) -> io::Result<Vec<Layer>> {
+        let mut layers = self.layers()?;
+        self.sort_layers(&mut layers)?;
+
+        Ok(layers)
+    }
+
+    /// Orders `layers` from `lowest` to `highest`.
+    ///
+    /// Some third-party exports don't have a [`LayerTable`]; on those, `layers` are kept on the
+    /// order they were found on the file system (see [`ValidationWarning::MissingLayerTable`]).
+    fn sort_layers(&self, layers: &mut [Layer]) -> io::Result<()> {
+        if let Some(laytbl) = self.optional_laytbl()? {
+            laytbl.sort_layers(layers);
+        }
+
+        Ok(())
+    }
+
+    /// Same as [`Sai::laytbl`], but returns [`None`] if the file doesn't have one.
+    fn optional_laytbl(&self) -> io::Result<Option<LayerTable>> {
+        match self.laytbl() {
+            Ok(laytbl) => Ok(Some(laytbl)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Flattens all the layers into a canvas sized image, with pixels in the RGBA color model with
+    /// pre-multiplied alpha.
+    fn flatten(&self) -> io::Result<Vec<u8>> {
+        let canvas = self.canvas()?;
+        let layers = self.sorted_layers()?;
+
+        Ok(compositor::composite(canvas.width, canvas.height, &layers))
+    }
+
+    // This methods are private for the moment.
+    //
+    // TODO: Add the ability to re-parse the Layer to get the layer data at a later time.
+
+    layers_no_decompress_method!(layers_no_decompress, "layers");
+    layers_no_decompress_method!(sublayers_no_decompress, "sublayers");
+}
+
+/// Adds the id of the layer stored on `entry` to `err`, so it is known which layer failed to be
+/// parsed.
+fn layer_error(entry: &FatEntry, err: io::Error) -> io::Error {
+    match entry
+        .name()
+        .and_then(|name| u32::from_str_radix(name, 16).ok())
+    {
+        Some(id) => io::Error::new(err.kind(), format!("layer {id}: {err}")),
+        None => err,
+    }
+}
+
+/// Scales `(width, height)` down (keeping its aspect ratio) until neither of its sides is bigger
+/// than `max_dim`; sizes that already fit are kept as they are.
+#[allow(clippy::cast_possible_truncation)]
+fn fit_within((width, height): (u32, u32), max_dim: u32) -> (u32, u32) {
+    let max_dim = max_dim.max(1);
+    // The result is never bigger than `max_dim`, but the multiplications can overflow a `u32`.
+    let scale = |side: u32, longest: u32| {
+        (u64::from(side) * u64::from(max_dim) / u64::from(longest)).max(1) as u32
+    };
+
+    if width.max(height) <= max_dim {
+        (width, height)
+    } else if width >= height {
+        (max_dim, scale(height, width))
+    } else {
+        (scale(width, height), max_dim)
+    }
+}
+
+/// Uses the name of the layer file (`entry`) when the `name` stream of `layer` is missing, or
+/// when it couldn't be decoded.
+fn with_fallback_name(mut layer: Layer, entry: &FatEntry) -> Layer {
+    if layer.name.is_none() {
+        layer.name = entry.name().map(str::to_owned);
+    }
+    layer
+}
+
+impl From<&[u8]> for Sai {
+    fn from(bytes: &[u8]) -> Self {
+        Self::with_fs(bytes.into())
+    }
+}
+
+impl From<Vec<u8>> for Sai {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::with_fs(bytes.into())
+    }
+}
+
+impl Display for Sai {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut layers = self.layers_no_decompress().unwrap();
+        self.sort_layers(&mut layers).unwrap();
+        layers.reverse();
+        write!(f, "{}", LayerTree::new(layers))
+    }
+}
+
+impl Debug for Sai {
+    /// Summarizes the document; any value that couldn't be read is shown as `?`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        /// Formats `Some` values with their `Display` implementation, and `None` as `?`.
+        struct OrUnknown<T>(Option<T>);
+
+        impl<T: Display> Debug for OrUnknown<T> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                match self.0 {
+                    Some(ref value) => value.fmt(f),
+                    None => f.write_str("?"),
+                }
+            }
+        }
+
+        let canvas = self.canvas().ok();
+        let canvas = canvas.as_ref();
+        let machine_hash = self.document().ok().map(|document| document.machine_hash);
+
+        f.debug_struct("Sai")
+            .field("width", &OrUnknown(canvas.map(|canvas| canvas.width)))
+            .field("height", &OrUnknown(canvas.map(|canvas| canvas.height)))
+            .field(
+                "layers",
+                &OrUnknown(self.laytbl().ok().map(|laytbl| laytbl.len())),
+            )
+            .field(
+                "machine_hash",
+                &OrUnknown(machine_hash.map(|hash| format!("{hash:#x}"))),
+            )
+            .field(
+                "dots_per_inch",
+                &OrUnknown(canvas.and_then(Canvas::dots_per_inch)),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internals::tests::{rename_root_entry, resource, SAMPLE as BYTES};
+
+    #[test]
+    fn trailing_bytes_works() -> io::Result<()> {
+        assert_eq!(Sai::from(BYTES).trailing_bytes(), 0);
+
+        let sai = Sai::from([BYTES, b"\r\n"].concat());
+        assert_eq!(sai.trailing_bytes(), 2);
+        assert_eq!(sai.document()?, Sai::from(BYTES).document()?);
+        assert_eq!(sai.layers()?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_root_works() -> io::Result<()> {
+        use crate::{
+            cipher::{TableBlock, PAGE_SIZE},
+            vfs::ROOT_INDEX,
+        };
+
+        // The root block is unused.
+        let mut bytes = BYTES.to_vec();
+        let mut table =
+            TableBlock::decrypt(<[u8; PAGE_SIZE]>::try_from(&bytes[..PAGE_SIZE]).unwrap(), 0)
+                .unwrap();
+        table[ROOT_INDEX].set_checksum(0);
+        bytes[..PAGE_SIZE].copy_from_slice(&*table.encrypt(0));
+
+        let mut sai = Sai::from(bytes);
+        for err in [sai.canvas().unwrap_err(), sai.layers().unwrap_err()] {
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+            assert!(err.to_string().ends_with("the root folder is empty"));
+        }
+        assert_eq!(
+            sai.layer_bounds(ID).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        // Writing a file makes the root block used again.
+        let bytes = [16u32, 100, 200, 0].map(u32::to_le_bytes).concat();
+        sai.write_file("/canvas", &bytes)?;
+        assert_eq!(sai.canvas()?.width, 100);
+        assert_eq!(
+            sai.layers().unwrap_err().to_string(),
+            "layers entry was not found"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn author_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let document = sai.document()?;
+
+        assert_eq!(document.id, 31);
+        assert_eq!(document.date_created, 1566984405);
+        assert_eq!(document.date_modified, 1567531929);
+        assert_eq!(document.machine_hash, 0x73851dcd1203b24d);
+        assert_eq!(
+            document.created_time(),
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(1566984405)
+        );
+        #[cfg(feature = "chrono")]
+        assert_eq!(document.modified_datetime().timestamp(), 1567531929);
+
+        assert!(sai.modified_since(1567531928)?);
+        assert!(!sai.modified_since(1567531929)?);
+        assert!(!sai.modified_since(u64::MAX)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn metadata_equals_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+
+        assert!(sai.metadata_equals(&sai)?);
+        assert!(sai.metadata_equals(&Sai::from(BYTES))?);
+        assert!(!sai.metadata_equals(&Sai::from(resource!("linework.sai").as_slice()))?);
+
+        let mut edited = Sai::from(BYTES);
+        let bytes = [16u32, 2250, 2250, 0].map(u32::to_le_bytes).concat();
+        edited.write_file("/canvas", &bytes)?;
+        assert!(!sai.metadata_equals(&edited)?);
+
+        Ok(())
+    }
+
+    const ID: u32 = 2;
+
+    #[test]
+    fn laybtl_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let laytbl = sai.laytbl()?;
+
+        assert_eq!(
+            laytbl[ID],
+            LayerRef {
+                id: ID,
+                kind: LayerKind::Regular,
+                tile_height: 78
+            }
+        );
+        assert_eq!(laytbl.get_index_of(ID), Some(0));
+        assert_eq!(laytbl.kind_of(ID), Some(LayerKind::Regular));
+        assert_eq!(laytbl.kind_of(ID + 1), None);
+        assert_eq!(laytbl.into_iter().count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn layers_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let layers = sai.layers_no_decompress()?;
+
+        assert_eq!(layers.len(), 1);
+
+        let layer = &layers[0];
+
+        assert_eq!(layer.kind, LayerKind::Regular);
+        assert_eq!(layer.id, ID);
+        assert_eq!(layer.bounds.x, -125);
+        assert_eq!(layer.bounds.y, -125);
+        assert_eq!(layer.bounds.width, 2464);
+        assert_eq!(layer.bounds.height, 2496);
+        assert_eq!(layer.opacity, 100);
+        assert!(layer.visible);
+        assert!(!layer.preserve_opacity);
+        assert!(!layer.clipping);
+        assert_eq!(layer.blending_mode, BlendingMode::Normal);
+        assert_eq!(layer.name, Some("Layer1".into()));
+        assert_eq!(layer.parent_set, None);
+        assert_eq!(layer.parent_layer, None);
+        assert_eq!(layer.open, None);
+        assert_eq!(layer.texture, None);
+        assert_eq!(layer.effect, None);
+        // FIX(Unavailable): layers_no_decompress
+        assert_eq!(layer.data, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unpremultiply_in_place_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let mut layer = sai.layers()?.swap_remove(0);
+        let straight = pixel_ops::premultiplied_to_straight(layer.data.as_ref().unwrap());
+
+        layer.unpremultiply_in_place();
+        assert_eq!(layer.data.as_ref(), Some(&straight));
+
+        Ok(())
+    }
+
+    #[test]
+    fn to_rgba_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let layer = sai.layers()?.swap_remove(0);
+        let (width, height, pixels) = layer.to_rgba().unwrap();
+        assert_eq!((width, height), (layer.bounds.width, layer.bounds.height));
+        assert_eq!(
+            pixels,
+            pixel_ops::premultiplied_to_straight(layer.data.as_ref().unwrap())
+        );
+
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        let mask = sai.sublayers()?.swap_remove(0);
+        let (width, height, pixels) = mask.to_rgba().unwrap();
+        assert_eq!(pixels.len(), (width * height) as usize);
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_data_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let layer = &sai.layers()?[0];
+
+        let data = sai.layer_data(ID)?;
+        assert_eq!(data.alpha_mode, Some(AlphaMode::Premultiplied));
+        assert_eq!(data.channels, 4);
+        assert_eq!((data.width, data.height), (2464, 2496));
+        assert_eq!(Some(&data.data), layer.data.as_ref());
+        assert_eq!(
+            data.data.len(),
+            (data.width * data.height) as usize * data.channels as usize
+        );
+
+        assert_eq!(
+            sai.layer_data(ID + 1).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        let sai = Sai::from(resource!("layersandgroups.sai").as_slice());
+        let set = sai.layers_no_decompress()?;
+        let set = set
+            .iter()
+            .find(|layer| layer.kind == LayerKind::Set)
+            .unwrap();
+        assert_eq!(
+            sai.layer_data(set.id).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn data_with_order_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let layer = sai.layers()?.swap_remove(0);
+        let rgba = layer
+            .data_with_order(pixel_ops::ChannelOrder::Rgba)
+            .unwrap();
+        let bgra = layer
+            .data_with_order(pixel_ops::ChannelOrder::Bgra)
+            .unwrap();
+
+        assert_eq!(rgba.as_ref(), layer.data.as_deref().unwrap());
+        assert_eq!(rgba.len(), bgra.len());
+        for (rgba, bgra) in rgba.chunks_exact(4).zip(bgra.chunks_exact(4)) {
+            assert_eq!([rgba[2], rgba[1], rgba[0], rgba[3]], bgra);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn thumbnail_is_current_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        assert!(sai.thumbnail_is_current(0.02)?);
+
+        let with_thumbnail = |thumbnail: Thumbnail| {
+            let sai = Sai::from(BYTES);
+            let _ = sai.thumbnail.set(thumbnail);
+            sai
+        };
+        let thumbnail = sai.thumbnail()?;
+
+        let stale = Thumbnail {
+            // Inverted colors.
+            pixels: thumbnail
+                .pixels
+                .chunks_exact(4)
+                .flat_map(|pixel| [!pixel[0], !pixel[1], !pixel[2], pixel[3]])
+                .collect(),
+            ..thumbnail.clone()
+        };
+        assert!(!with_thumbnail(stale).thumbnail_is_current(0.02)?);
+
+        let canvas = sai.canvas()?;
+        let bigger = Thumbnail {
+            width: canvas.width + 1,
+            height: 1,
+            pixels: vec![255; (canvas.width as usize + 1) * 4],
+        };
+        assert!(!with_thumbnail(bigger).thumbnail_is_current(0.02)?);
+
+        let truncated = Thumbnail {
+            pixels: thumbnail.pixels[4..].to_vec(),
+            ..thumbnail
+        };
+        assert_eq!(
+            with_thumbnail(truncated)
+                .thumbnail_is_current(0.02)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn layers_with_limits_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        assert_eq!(sai.layers_with_limits(DecodeLimits::default())?.len(), 1);
+
+        let limits = DecodeLimits {
+            max_total_bytes: 1024,
+            ..Default::default()
+        };
+        let err = sai.layers_with_limits(limits).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+
+        let limits = DecodeLimits {
+            max_pixels: 32 * 32,
+            ..Default::default()
+        };
+        let err = sai.layers_with_limits(limits).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+
+        Ok(())
+    }
+
+    #[test]
+    fn into_layers_works() -> io::Result<()> {
+        let layers = Sai::from(BYTES).into_layers()?;
+
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].id, ID);
+        assert!(layers[0].data.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn selection_source_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        assert_eq!(sai.selection_source()?, None);
+        assert_eq!(sai.find_layer(Some(0xdead))?, None);
+
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        let layer = sai.selection_source()?.unwrap();
+        assert_eq!(layer.name.as_deref(), Some("selection_source"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn thumbnail_to_image_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let image = sai.thumbnail()?.to_image();
+
+        assert_eq!(image.dimensions(), (140, 140));
+
+        Ok(())
+    }
+
+    #[test]
+    fn debug_works() {
+        let sai = Sai::from(BYTES);
+        let debug = format!("{sai:?}");
+
+        assert!(debug.contains("width: 2250"));
+        assert!(debug.contains("height: 2250"));
+        assert!(debug.contains("layers: 1"));
+    }
+
+    #[test]
+    fn canvas_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let canvas = sai.canvas()?;
+
+        assert_eq!(canvas.alignment, 16);
+        assert_eq!(canvas.width, 2250);
+        assert_eq!(canvas.height, 2250);
+        assert_eq!(canvas.resolution, Some(72 << 16));
+        assert_eq!(canvas.dots_per_inch(), Some(72.0));
+        assert_eq!(canvas.size_unit, Some(SizeUnit::Pixels));
+        assert_eq!(canvas.resolution_unit, Some(ResolutionUnit::PixelsInch));
+        assert_eq!(canvas.selection_source, None);
+        assert_eq!(canvas.selected_layer, Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn canvas_pixels_cm_works() -> io::Result<()> {
+        let mut bytes = [16u32, 512, 512].map(u32::to_le_bytes).concat();
+        bytes.extend_from_slice(b"oser");
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+        bytes.extend_from_slice(&(30u32 << 16).to_le_bytes());
+        bytes.extend_from_slice(&[2, 0, 1, 0]);
+        bytes.extend_from_slice(&[0; 4]);
+
+        let canvas = Canvas::from_reader(&mut bytes.as_slice())?;
+
+        assert_eq!(canvas.size_unit, Some(SizeUnit::Centimeters));
+        assert_eq!(canvas.resolution_unit, Some(ResolutionUnit::PixelsCm));
+        assert_eq!(canvas.resolution(), Some(30.0));
+        assert_eq!(canvas.dots_per_inch(), Some(76.2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn subtbl_is_err() {
+        let sai = Sai::from(BYTES);
+        assert!(sai.subtbl().is_err());
+    }
+
+    #[test]
+    fn sublayers_is_err() {
+        let sai = Sai::from(BYTES);
+        assert!(sai.sublayers().is_err());
+    }
+
+    #[test]
+    fn verify_integrity_works() {
+        use crate::cipher::PAGE_SIZE;
+
+        assert!(Sai::from(BYTES).verify_integrity().is_ok());
+        assert!(Sai::from(resource!("layersandgroups.sai").as_slice())
+            .verify_integrity()
+            .is_ok());
+
+        let corrupt = |indices: &[usize]| {
+            let mut bytes = BYTES.to_vec();
+            for index in indices {
+                bytes[index * PAGE_SIZE + 100] ^= 0xFF;
+            }
+            Sai::from(bytes).verify_integrity().unwrap_err()
+        };
+        let indices = |mismatches: Vec<(usize, ChecksumMismatchError)>| {
+            mismatches
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(indices(corrupt(&[4])), [4]);
+        assert_eq!(indices(corrupt(&[3, 600])), [3, 600]);
+        // The whole first sector is skipped.
+        assert_eq!(indices(corrupt(&[0, 4, 600])), [0, 600]);
+
+        let mismatches = corrupt(&[4]);
+        let err = &mismatches[0].1;
+        assert_ne!(err.actual(), err.expected());
+    }
+
+    #[test]
+    fn validate_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        assert_eq!(sai.validate()?, []);
+        assert_eq!(sai.suggest_canvas_fixes(), []);
+
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        assert_eq!(sai.validate()?, []);
+
+        let bytes = rename_root_entry(resource!("layerproperties.sai"), "sublayers", "xublayers");
+        assert_eq!(
+            Sai::from(bytes.as_slice()).validate()?,
+            [ValidationWarning::MissingMask(18)]
+        );
+
+        let bytes = rename_root_entry(resource!("layerproperties.sai"), "subtbl", "xubtbl");
+        assert_eq!(
+            Sai::from(bytes.as_slice()).validate()?,
+            [ValidationWarning::UnlistedMask(18)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn composite_works() -> io::Result<()> {
+        let sai = Sai::from(resource!("layerfullred.sai").as_slice());
+        let canvas = sai.canvas()?;
+        let image = sai.composite()?;
+
+        assert_eq!((image.width, image.height), (canvas.width, canvas.height));
+        assert_eq!(
+            image.data.len(),
+            (canvas.width * canvas.height * 4) as usize
+        );
+        assert!(image
+            .data
+            .chunks_exact(4)
+            .all(|pixel| pixel == [255, 0, 0, 255]));
+
+        // `straight` alpha.
+        let mut sai = sai;
+        sai.edit_layers(|layer| layer.set_opacity(50))?;
+        let image = sai.composite()?;
+        assert!(image
+            .data
+            .chunks_exact(4)
+            .all(|pixel| pixel == [255, 0, 0, 128]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn flattened_has_transparency_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        assert!(sai.flattened_has_transparency()?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn export_layers_par_works() -> io::Result<()> {
+        let dir = std::env::temp_dir().join("saire-export-layers-par");
+        std::fs::create_dir_all(&dir)?;
+
+        let paths = Sai::from(BYTES).export_layers_par(&dir)?;
+        assert_eq!(paths, [dir.join(format!("{ID:0>8x}.png"))]);
+        assert!(paths[0].exists());
+
+        std::fs::remove_dir_all(dir)
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn export_spritesheet_works() -> io::Result<()> {
+        let path = std::env::temp_dir().join("saire-spritesheet.png");
+        let sai = Sai::from(BYTES);
+        let meta = sai.export_spritesheet(&path)?;
+        std::fs::remove_file(path)?;
+
+        let content = sai.layers()?[0].content_bounds().unwrap();
+        assert_eq!((meta.width, meta.height), (content.width, content.height));
+        assert_eq!(
+            meta.sprites,
+            [AtlasSprite {
+                id: ID,
+                name: Some("Layer1".into()),
+                x: 0,
+                y: 0,
+                width: content.width,
+                height: content.height,
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn entries_works() -> io::Result<()> {
+        let mut sai = Sai::from(BYTES);
+        sai.create_dir("/extra")?;
+        sai.write_file("/extra/notes", b"unknown")?;
+
+        let entries = sai.entries().collect::<io::Result<Vec<_>>>()?;
+        let names = entries
+            .iter()
+            .map(|entry| (entry.name().unwrap(), entry.kind().unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(names.len(), 8);
+        let extra = names.iter().position(|e| e == &("extra", FatKind::Folder));
+        assert_eq!(names[extra.unwrap() + 1], ("notes", FatKind::File));
+        assert!(names.contains(&("00000002", FatKind::File)));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "webp")]
+    fn to_webp_works() -> io::Result<()> {
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        let path = std::env::temp_dir().join("saire-composite.webp");
+
+        for quality in [None, Some(80.0)] {
+            sai.to_webp(&path, quality)?;
+            let bytes = std::fs::read(&path)?;
+
+            assert_eq!(&bytes[..4], b"RIFF");
+            assert_eq!(&bytes[8..12], b"WEBP");
+            assert_eq!(
+                u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+                bytes.len() - 8
+            );
+            // `VP8L` is the lossless bitstream; lossy images with alpha are `VP8X` instead.
+            assert_eq!(&bytes[12..16] == b"VP8L", quality.is_none());
+        }
+        std::fs::remove_file(path)?;
+
+        assert_eq!(
+            sai.to_webp("unused.webp", Some(101.0)).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "tiff")]
+    fn to_tiff_works() -> io::Result<()> {
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        let path = std::env::temp_dir().join("saire-layers.tiff");
+        sai.to_tiff(&path)?;
+        let bytes = std::fs::read(&path)?;
+        std::fs::remove_file(path)?;
+
+        let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..][..4].try_into().unwrap());
+        assert_eq!(&bytes[..4], b"II\x2A\x00");
+
+        // Every page as a map of `tag` to the offset of its value ( or the value itself ).
+        let mut pages = Vec::new();
+        let mut ifd = u32_at(4) as usize;
+        while ifd != 0 {
+            let count = u16_at(ifd) as usize;
+            let entries: HashMap<_, _> = (0..count)
+                .map(|idx| ifd + 2 + idx * 12)
+                .map(|entry| (u16_at(entry), u32_at(entry + 8)))
+                .collect();
+            pages.push(entries);
+            ifd = u32_at(ifd + 2 + count * 12) as usize;
+        }
+        let ascii = |offset: u32| {
+            let offset = offset as usize;
+            let len = bytes[offset..].iter().position(|&byte| byte == 0).unwrap();
+            String::from_utf8_lossy(&bytes[offset..][..len]).into_owned()
+        };
+
+        let canvas = sai.canvas()?;
+        let layers = sai.sorted_layers()?;
+        let regular = layers
+            .iter()
+            .filter(|layer| layer.kind == LayerKind::Regular)
+            .count();
+        assert_eq!(pages.len(), 1 + regular + 1);
+        assert_eq!(
+            (pages[0][&256], pages[0][&257]),
+            (canvas.width, canvas.height)
+        );
+        assert_eq!(pages[0][&277] & 0xFFFF, 4);
+
+        let first = layers
+            .iter()
+            .find(|layer| layer.kind == LayerKind::Regular)
+            .unwrap();
+        let bounds = first.bounds;
+        let (x, y) = first.data_origin();
+        assert_eq!(ascii(pages[1][&285]), *first.name.as_ref().unwrap());
+        assert_eq!(
+            ascii(pages[1][&270]),
+            format!("{x},{y},{},{}", bounds.width, bounds.height)
+        );
+        assert_eq!(
+            (pages[1][&256], pages[1][&257]),
+            (bounds.width, bounds.height)
+        );
+        // The mask is the last page, as grayscale.
+        assert_eq!(pages.last().unwrap()[&277] & 0xFFFF, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn flatten_groups_works() -> io::Result<()> {
+        let sai = Sai::from(resource!("layersandgroups.sai").as_slice());
+        let layers = sai.flatten_groups()?;
+
+        assert!(layers.iter().all(|layer| layer.parent_set.is_none()));
+        assert!(layers.iter().all(|layer| layer.kind != LayerKind::Set));
+
+        let set = layers.iter().find(|layer| layer.id == 5).unwrap();
+        assert_eq!(set.kind, LayerKind::Regular);
+        assert_eq!(set.data_origin(), (0, 0));
+        assert!(set.content_bounds().is_some());
+        assert!(layers.iter().all(|layer| !(6..=9).contains(&layer.id)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_stats_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        assert_eq!(
+            sai.layer_stats()?,
+            BTreeMap::from([(LayerKind::Regular, 1)])
+        );
+
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        assert_eq!(
+            sai.layer_stats()?,
+            BTreeMap::from([
+                (LayerKind::Regular, 18),
+                (LayerKind::Mask, 1),
+                (LayerKind::Set, 1)
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_child_counts_works() -> io::Result<()> {
+        assert_eq!(Sai::from(BYTES).set_child_counts()?, HashMap::new());
+
+        let sai = Sai::from(resource!("layersandgroups.sai").as_slice());
+        assert_eq!(sai.set_child_counts()?, HashMap::from([(5, 4)]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn for_each_layer_tile_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let layer = sai.layers()?.remove(0);
+        let data = layer.data.as_ref().unwrap();
+
+        let mut coords = Vec::new();
+        let mut pixels = vec![0; data.len()];
+        sai.for_each_layer_tile(|id, coord, tile| {
+            assert_eq!(id, ID);
+            let size = TileCoord::SIZE as usize;
+            for (row, src) in tile.chunks_exact(size * 4).enumerate() {
+                let offset = (coord.y as usize * size + row) * layer.bounds.width as usize
+                    + coord.x as usize * size;
+                pixels[offset * 4..][..size * 4].copy_from_slice(src);
+            }
+            coords.push(coord);
+        })?;
+
+        assert!(coords.contains(&TileCoord { x: 0, y: 0 }));
+        assert!(coords
+            .windows(2)
+            .all(|pair| (pair[0].y, pair[0].x) < (pair[1].y, pair[1].x)));
+        assert_eq!(&pixels, data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn masks_by_layer_works() -> io::Result<()> {
+        assert_eq!(Sai::from(BYTES).masks_by_layer()?, HashMap::new());
+
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        let masks = sai.masks_by_layer()?;
+        assert_eq!(masks, HashMap::from([(15, 18)]));
+        // An unmasked layer.
+        assert!(!masks.contains_key(&16));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    #[allow(clippy::cast_possible_truncation)]
+    fn mask_to_png16_works() -> io::Result<()> {
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        let path = std::env::temp_dir().join("saire-mask-16.png");
+        sai.mask_to_png16(18, Some(&path))?;
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path)?);
+        let mut reader = decoder.read_info()?;
+        let mut bytes = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut bytes)?;
+        std::fs::remove_file(path)?;
+
+        assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+        assert_eq!(info.color_type, png::ColorType::Grayscale);
+
+        let mask = sai
+            .sublayers()?
+            .into_iter()
+            .find(|layer| layer.id == 18)
+            .unwrap();
+        assert_eq!(
+            (info.width, info.height),
+            (mask.bounds.width, mask.bounds.height)
+        );
+
+        // Scaling the 16-bit values down should give back (almost) the same 8-bit ones.
+        let data = mask.data.unwrap();
+        let pixels: Vec<u16> = bytes[..info.buffer_size()]
+            .chunks_exact(2)
+            .map(|pixel| u16::from_be_bytes([pixel[0], pixel[1]]))
+            .collect();
+        assert_eq!(pixels.len(), data.len());
+        for (&pixel, &expected) in pixels.iter().zip(&data) {
+            let scaled = (u32::from(pixel) * 255 / u32::from(u16::MAX)) as u8;
+            assert!(scaled.abs_diff(expected) <= 1, "{pixel} {expected}");
+        }
+        assert!(pixels.contains(&u16::MAX));
+
+        // Not a mask.
+        assert_eq!(
+            sai.mask_to_png16(15, None::<&str>).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn canvas_and_layers_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let (canvas, layers) = sai.canvas_and_layers()?;
+
+        assert_eq!(canvas, sai.canvas()?);
+        assert_eq!(layers.len(), sai.layers()?.len());
+        assert_eq!(layers[0].id, ID);
+        assert_eq!(canvas.selected_layer, Some(layers[0].id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_bounds_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+
+        assert_eq!(
+            sai.layer_bounds(ID)?,
+            LayerBounds {
+                x: -125,
+                y: -125,
+                width: 2464,
+                height: 2496,
+            }
+        );
+        assert!(sai.layer_bounds(ID + 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_data_checksum_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let checksum = sai.layer_data_checksum(ID)?;
+
+        assert_eq!(Sai::from(BYTES).layer_data_checksum(ID)?, checksum);
+        assert!(sai.layer_data_checksum(ID + 1).is_err());
+
+        let sai = Sai::from(resource!("layersandgroups.sai").as_slice());
+        assert_ne!(sai.layer_data_checksum(2)?, sai.layer_data_checksum(3)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn content_fingerprint_works() -> io::Result<()> {
+        let fingerprint = Sai::from(BYTES).content_fingerprint()?;
+
+        assert_eq!(Sai::from(BYTES).content_fingerprint()?, fingerprint);
+        assert_ne!(
+            Sai::from(resource!("layersandgroups.sai").as_slice()).content_fingerprint()?,
+            fingerprint
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_laytbl_works() -> io::Result<()> {
+        let bytes = rename_root_entry(BYTES, "laytbl", "xaytbl");
+        let sai = Sai::from(bytes.as_slice());
+
+        assert!(sai.laytbl().is_err());
+        assert_eq!(sai.validate()?, [ValidationWarning::MissingLayerTable]);
+
+        let layers = sai.layers_sorted_by(SortKey::StackOrder)?;
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].id, ID);
+        assert!(!sai.to_string().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn edit_layers_works() -> io::Result<()> {
+        let mut sai = Sai::from(resource!("layersandgroups.sai").as_slice());
+        let layers = sai.layers()?;
+
+        sai.edit_layers(|layer| {
+            layer.set_opacity(50);
+            if layer.layer().id == 2 {
+                layer.set_visible(false);
+                layer.set_blending_mode(BlendingMode::Multiply);
+                layer.set_name("renamed");
+            }
+        })?;
+
+        let mut bytes = Vec::new();
+        sai.write_to(&mut bytes)?;
+
+        for sai in [&sai, &Sai::from(bytes.as_slice())] {
+            let edited = sai.layers()?;
+            assert_eq!(edited.len(), layers.len());
+
+            for (layer, edited) in layers.iter().zip(&edited) {
+                assert_eq!(edited.opacity, 50);
+                assert_eq!(edited.data, layer.data);
+            }
+
+            let layer = edited.iter().find(|layer| layer.id == 2).unwrap();
+            assert!(!layer.visible);
+            assert_eq!(layer.blending_mode, BlendingMode::Multiply);
+            assert_eq!(layer.name.as_deref(), Some("renamed"));
+        }
+
+        assert!(sai
+            .edit_layers(|layer| layer.set_name("a".repeat(300)))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_file_works() -> io::Result<()> {
+        let mut sai = Sai::from(BYTES);
+        let canvas = sai.canvas()?;
+
+        // `alignment`, `width`, `height`, and no streams.
+        let bytes = [16u32, 100, 200, 0].map(u32::to_le_bytes).concat();
+        sai.write_file("/canvas", &bytes)?;
+
+        let mut written = Vec::new();
+        sai.write_to(&mut written)?;
+
+        for sai in [&sai, &Sai::from(written.as_slice())] {
+            let edited = sai.canvas()?;
+            assert_eq!((edited.width, edited.height), (100, 200));
+            assert_eq!(edited.resolution, None);
+            assert_eq!(sai.layers()?, Sai::from(BYTES).layers()?);
+        }
+        assert_ne!(sai.canvas()?, canvas);
+
+        Ok(())
+    }
+
+    #[test]
+    fn clone_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES.to_vec());
+        let clone = sai.clone();
+
+        assert_eq!(clone.layers()?, sai.layers()?);
+        assert_eq!(clone.canvas()?, sai.canvas()?);
+
+        let mut sai = sai;
+        sai.edit_layers(|layer| layer.set_opacity(10))?;
+        assert_eq!(sai.clone().layers()?[0].opacity, 10);
+        assert_eq!(clone.layers()?[0].opacity, 100);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_layer_names_fall_back_to_the_entry_name() -> io::Result<()> {
+        let mut sai = Sai::from(BYTES);
+        let index = sai.get_layer_entry(ID)?.next_block() as usize;
+        let mut page = *sai.fs.read_data(index).0.into_virtual_page();
+        // Invalid utf-8, and no nul terminator.
+        let name = page.windows(4).position(|tag| tag == b"eman").unwrap();
+        let size = u32::from_le_bytes(page[name + 4..name + 8].try_into().unwrap()) as usize;
+        page[name + 8..name + 8 + size].fill(0xFF);
+        sai.fs.write_data(index, page.into());
+
+        let expected = format!("{ID:0>8x}");
+        assert_eq!(sai.get_layer(ID, false)?.name.as_deref(), Some(&*expected));
+        assert_eq!(sai.layers()?[0].name.as_deref(), Some(&*expected));
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_errors_have_the_layer_id() {
+        let mut sai = Sai::from(BYTES);
+        let index = sai.get_layer_entry(ID).unwrap().next_block() as usize;
+        let mut page = *sai.fs.read_data(index).0.into_virtual_page();
+        // Invalid `BlendingMode`.
+        page[33..Layer::HEADER_SIZE].copy_from_slice(b"????");
+        sai.fs.write_data(index, page.into());
+
+        let err = sai.layers().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains(&format!("layer {ID}")));
+        assert!(sai.layer_bounds(ID).is_err());
+    }
+
+    /// Creates a file system whose `/layers` folder holds `count` [`LayerKind::Set`] layers, with
+    /// ids from `0` to `count`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn many_layers(count: usize) -> Vec<u8> {
+        use crate::cipher::{DataBlock, TableBlock, VirtualPage, BLOCKS_PER_SECTOR, PAGE_SIZE};
+
+        const ENTRY_SIZE: usize = 64;
+        const ENTRIES_PER_BLOCK: usize = PAGE_SIZE / ENTRY_SIZE;
+
+        let entry = |name: &str, kind: u8, next_block: usize, size: u32| {
+            let mut entry = [0; ENTRY_SIZE];
+            entry[..4].copy_from_slice(&0x8000_0000u32.to_le_bytes());
+            entry[4..][..name.len()].copy_from_slice(name.as_bytes());
+            entry[38] = kind;
+            entry[40..44].copy_from_slice(&(next_block as u32).to_le_bytes());
+            entry[44..48].copy_from_slice(&size.to_le_bytes());
+            entry
+        };
+        // Gets the index of the nth page, skipping the `TableBlock`s at the start of each sector.
+        let page_index = |page: usize| page + (page - 1) / (BLOCKS_PER_SECTOR - 1);
+
+        let folder_blocks = count.div_ceil(ENTRIES_PER_BLOCK);
+        let first_layer = 3 + folder_blocks;
+        let mut pages = vec![[0; PAGE_SIZE]; first_layer + count];
+
+        pages[2][..ENTRY_SIZE].copy_from_slice(&entry("layers", 0x10, page_index(3), 0));
+        for id in 0..count {
+            let (block, slot) = (3 + id / ENTRIES_PER_BLOCK, id % ENTRIES_PER_BLOCK);
+            let size = Layer::HEADER_SIZE as u32 + 4;
+            pages[block][slot * ENTRY_SIZE..][..ENTRY_SIZE].copy_from_slice(&entry(
+                &format!("{id:0>8x}"),
+                0x80,
+                page_index(first_layer + id),
+                size,
+            ));
+
+            let layer = &mut pages[first_layer + id];
+            layer[..4].copy_from_slice(&8u32.to_le_bytes());
+            layer[4..8].copy_from_slice(&(id as u32).to_le_bytes());
+            layer[33..Layer::HEADER_SIZE].copy_from_slice(b"ssap");
+        }
+
+        let len = page_index(pages.len() - 1) + 1;
+        let mut tables = vec![[0; PAGE_SIZE]; len.div_ceil(BLOCKS_PER_SECTOR)];
+        let mut bytes = vec![0; len * PAGE_SIZE];
+        for (page, data) in pages.into_iter().enumerate().skip(1) {
+            let index = page_index(page);
+            let block = DataBlock::from(VirtualPage::from(data));
+            let table = &mut tables[index / BLOCKS_PER_SECTOR];
+            let entry = (index % BLOCKS_PER_SECTOR) * 8;
+            table[entry..][..4].copy_from_slice(&block.checksum().to_le_bytes());
+            // Chains the blocks of the `/layers` folder.
+            let next_block = if (3..first_layer - 1).contains(&page) {
+                page_index(page + 1) as u32
+            } else {
+                0
+            };
+            table[entry + 4..][..4].copy_from_slice(&next_block.to_le_bytes());
+            bytes[index * PAGE_SIZE..][..PAGE_SIZE].copy_from_slice(&*block.encrypt(None));
+        }
+        for (sector, table) in tables.into_iter().enumerate() {
+            let table_index = sector * BLOCKS_PER_SECTOR;
+            let table = TableBlock::from(VirtualPage::from(table)).encrypt(table_index as u32);
+            bytes[table_index * PAGE_SIZE..][..PAGE_SIZE].copy_from_slice(&*table);
+        }
+
+        bytes
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn layers_works_across_threads() -> io::Result<()> {
+        let sai = Sai::from(resource!("layersandgroups.sai").as_slice());
+        let expected = Sai::from(resource!("layersandgroups.sai").as_slice()).layers()?;
+
+        std::thread::scope(|scope| {
+            let handles = (0..4)
+                .map(|_| scope.spawn(|| (sai.layers(), sai.thumbnail())))
+                .collect::<Vec<_>>();
+            for handle in handles {
+                let (layers, thumbnail) = handle.join().expect("thread didn't panic");
+                assert_eq!(layers?, expected);
+                assert_eq!(thumbnail?.pixels, sai.thumbnail()?.pixels);
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn layers_works_with_more_than_64_entries() -> io::Result<()> {
+        let sai = Sai::from(many_layers(100));
+        let layers = sai.layers()?;
+
+        assert!(layers.iter().map(|layer| layer.id).eq(0..100));
+        assert!(layers.iter().all(|layer| layer.kind == LayerKind::Set));
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_streaming_works() -> io::Result<()> {
+        use crate::cipher::{BLOCKS_PER_SECTOR, PAGE_SIZE};
+
+        // ~6 sectors ( 12 MiB ), more than what `STREAMING_TABLE_CAPACITY` can hold.
+        let bytes = many_layers(3000);
+        assert!(bytes.len() / PAGE_SIZE > Sai::STREAMING_TABLE_CAPACITY * BLOCKS_PER_SECTOR);
+
+        let path = std::env::temp_dir().join("saire-open-streaming.sai");
+        std::fs::write(&path, &bytes)?;
+
+        let sai = Sai::open_streaming(&path);
+        let layers = sai.layers()?;
+        assert!(layers.iter().map(|layer| layer.id).eq(0..3000));
+        assert!(sai.fs.cached_tables() <= Sai::STREAMING_TABLE_CAPACITY);
+
+        let sai = Sai::new_unchecked(&path);
+        assert_eq!(sai.layers()?, layers);
+        assert!(sai.fs.cached_tables() > Sai::STREAMING_TABLE_CAPACITY);
+
+        std::fs::remove_file(path)
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn open_mmap_works() -> io::Result<()> {
+        let path = std::env::temp_dir().join("saire-open-mmap.sai");
+        std::fs::write(&path, BYTES)?;
+
+        // SAFETY: Nothing else writes to the file until the `Sai` is dropped.
+        let sai = unsafe { Sai::open_mmap(&path) };
+        let expected = Sai::from(BYTES);
+        assert_eq!(sai.canvas()?, expected.canvas()?);
+        assert_eq!(sai.layers()?, expected.layers()?);
+        assert_eq!(sai.clone().layers()?, expected.layers()?);
+
+        let mut bytes = Vec::new();
+        sai.write_to(&mut bytes)?;
+        assert!(bytes == BYTES);
+
+        drop(sai);
+        std::fs::remove_file(path)
+    }
+
+    #[test]
+    fn new_in_memory_works() -> io::Result<()> {
+        let bytes = resource!("layersandgroups.sai");
+        let expected = Sai::from(bytes.as_slice());
+
+        let mut sai = Sai::new_in_memory(bytes.to_vec());
+        assert_eq!(sai.canvas()?, expected.canvas()?);
+        assert_eq!(sai.layers()?, expected.layers()?);
+        assert_eq!(sai.thumbnail()?.pixels, expected.thumbnail()?.pixels);
+
+        let clone = sai.clone();
+        sai.edit_layers(|layer| layer.set_opacity(10))?;
+        sai.write_file("/extra", b"extra")?;
+        assert!(sai
+            .layers_no_decompress()?
+            .iter()
+            .all(|layer| layer.opacity == 10));
+        assert_eq!(clone.layers()?, expected.layers()?);
+
+        let mut written = Vec::new();
+        sai.write_to(&mut written)?;
+        assert_eq!(
+            Sai::from(written).layers_no_decompress()?,
+            sai.layers_no_decompress()?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fat_works() -> io::Result<()> {
+        use crate::cipher::{DataBlock, TableBlock, PAGE_SIZE};
+
+        let fat = Sai::from(BYTES).fat()?;
+        assert_eq!(fat.len(), BYTES.len() / PAGE_SIZE);
+
+        let page = |index: usize| -> [u8; PAGE_SIZE] {
+            BYTES[index * PAGE_SIZE..][..PAGE_SIZE].try_into().unwrap()
+        };
+        let table = TableBlock::decrypt(page(0), 0).unwrap();
+        assert_eq!(fat[..fat.len().min(512)], table[..fat.len().min(512)]);
+
+        let root = DataBlock::decrypt(page(2), fat[2].checksum()).unwrap();
+        assert_eq!(root[3].name(), Some("layers"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn layers_sorted_by_works() -> io::Result<()> {
+        let sai = Sai::from(resource!("layersandgroups.sai").as_slice());
+        let ids = |key| -> io::Result<Vec<_>> {
+            Ok(sai
+                .layers_sorted_by(key)?
+                .into_iter()
+                .map(|layer| layer.id)
+                .collect())
+        };
+
+        assert_eq!(ids(SortKey::Id)?, (2..=11).collect::<Vec<_>>());
+        assert_eq!(ids(SortKey::StackOrder)?, [11, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+
+        assert_eq!(ids(SortKey::Name)?, [4, 6, 3, 5, 9, 8, 2, 10, 11, 7]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn raw_layer_stream_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let streams = sai.raw_layer_stream(ID)?;
+
+        let (_, name) = streams.iter().find(|(tag, _)| tag == "name").unwrap();
+        assert_eq!(name.len(), 256);
+        assert!(name.starts_with(b"Layer1\0"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_thumbnail_works() -> io::Result<()> {
+        const MAX_DIM: u32 = 64;
+
+        let sai = Sai::from(BYTES);
+        let (width, height, pixels) = sai.layer_thumbnail(ID, MAX_DIM)?;
+
+        assert!(width <= MAX_DIM);
+        assert!(height <= MAX_DIM);
+        assert_eq!(width.max(height), MAX_DIM);
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fit_within_doesnt_overflow() {
+        assert_eq!(fit_within((5000, 2500), 1_000_000), (5000, 2500));
+        assert_eq!(fit_within((100_000, 50_000), 99_999), (99_999, 49_999));
+        assert_eq!(
+            fit_within((u32::MAX, u32::MAX - 1), u32::MAX - 1).0,
+            u32::MAX - 1
+        );
+        assert_eq!(fit_within((1, 4000), 64), (1, 64));
+        assert_eq!(fit_within((10, 10), 0), (1, 1));
+    }
+
+    #[test]
+    fn thumbnail_works() -> io::Result<()> {
+        let sai = Sai::from(BYTES);
+        let thumbnail = sai.thumbnail()?;
+
+        assert_eq!(thumbnail.width, 140);
         assert_eq!(thumbnail.height, 140);
         assert_eq!(thumbnail.pixels.len(), 78400);
 
+        assert!(sai.thumbnail.get().is_some());
+        assert_eq!(sai.thumbnail()?, thumbnail);
+
         Ok(())
     }
 
@@ -269,10 +2560,10 @@ mod tests {
         let sai = Sai::from(BYTES);
         assert_eq!(
             format!("\n{sai}"),
-            r#"
+            r"
 .
 └─ Layer1
-"#
-        )
+"
+        );
     }
 }
@@ -0,0 +1,88 @@
+/// Where a layer was placed inside of a spritesheet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtlasSprite {
+    /// [`Layer::id`] of the packed layer.
+    ///
+    /// [`Layer::id`]: crate::models::layer::Layer::id
+    pub id: u32,
+    /// [`Layer::name`] of the packed layer.
+    ///
+    /// [`Layer::name`]: crate::models::layer::Layer::name
+    pub name: Option<String>,
+    /// Left side of the sprite, relative to the atlas.
+    pub x: u32,
+    /// Top side of the sprite, relative to the atlas.
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Metadata of a spritesheet created by [`Sai::export_spritesheet`].
+///
+/// [`Sai::export_spritesheet`]: crate::Sai::export_spritesheet
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AtlasMeta {
+    /// Width of the whole atlas image.
+    pub width: u32,
+    /// Height of the whole atlas image.
+    pub height: u32,
+    /// Sprites in the same order as the layers were given to pack.
+    pub sprites: Vec<AtlasSprite>,
+}
+
+impl AtlasMeta {
+    /// Places `sprites` (`id`, `name`, `width`, `height`) into an atlas using shelf packing.
+    ///
+    /// Sprites are placed from the tallest to the shortest, from left to right, and a new shelf
+    /// is opened when the current one can't fit the next sprite. The atlas is made (roughly)
+    /// square, but never narrower than the widest sprite.
+    #[cfg_attr(not(feature = "png"), allow(dead_code))]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub(crate) fn pack(sprites: impl IntoIterator<Item = (u32, Option<String>, u32, u32)>) -> Self {
+        let mut sprites: Vec<_> = sprites
+            .into_iter()
+            .map(|(id, name, width, height)| AtlasSprite {
+                id,
+                name,
+                x: 0,
+                y: 0,
+                width,
+                height,
+            })
+            .collect();
+
+        let area: u64 = sprites
+            .iter()
+            .map(|sprite| u64::from(sprite.width) * u64::from(sprite.height))
+            .sum();
+        let widest = sprites.iter().map(|sprite| sprite.width).max().unwrap_or(0);
+        let max_width = widest.max((area as f64).sqrt().ceil() as u32);
+
+        let mut order: Vec<_> = (0..sprites.len()).collect();
+        order.sort_by_key(|&idx| std::cmp::Reverse(sprites[idx].height));
+
+        let (mut x, mut y, mut shelf_height, mut width) = (0, 0, 0, 0);
+        for idx in order {
+            let sprite = &mut sprites[idx];
+            if x + sprite.width > max_width {
+                (x, y) = (0, y + shelf_height);
+                shelf_height = 0;
+            }
+
+            (sprite.x, sprite.y) = (x, y);
+            x += sprite.width;
+            width = width.max(x);
+            shelf_height = shelf_height.max(sprite.height);
+        }
+
+        Self {
+            width,
+            height: y + shelf_height,
+            sprites,
+        }
+    }
+}
@@ -60,7 +60,7 @@ impl TryFrom<[u8; 4]> for StreamTag {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Canvas {
     /// Always 0x10(16), possibly bpc or alignment
     pub alignment: u32,
@@ -72,7 +72,9 @@ pub struct Canvas {
     // Decided to make the `stream` data `Option`s, because I'm not really sure if they need to be
     // present all the time.
     //
-    pub dots_per_inch: Option<f32>,
+    /// The resolution of the image as a 16.16 fixed point integer, in [`Canvas::resolution_unit`]
+    /// (pixels per inch, or pixels per centimeter); see [`Canvas::dots_per_inch`].
+    pub resolution: Option<u32>,
     pub size_unit: Option<SizeUnit>,
     pub resolution_unit: Option<ResolutionUnit>,
     /// ID of layer marked as the selection source.
@@ -82,6 +84,11 @@ pub struct Canvas {
 }
 
 impl Canvas {
+    /// Reads a `Canvas` from the bytes of the `canvas` file.
+    ///
+    /// # Errors
+    ///
+    /// - If `reader` doesn't hold a valid `canvas` file.
     pub fn from_reader<R>(reader: &mut R) -> io::Result<Self>
     where
         R: Read,
@@ -100,7 +107,7 @@ impl Canvas {
             alignment,
             width,
             height,
-            dots_per_inch: None,
+            resolution: None,
             size_unit: None,
             resolution_unit: None,
             selection_source: None,
@@ -109,15 +116,12 @@ impl Canvas {
 
         while let Some((tag, size)) = reader.read_stream_header().transpose()? {
             let Some(tag) = tag else {
-                reader.skip(size as usize)?;
+                reader.skip_stream_body(size)?;
                 continue;
             };
             match tag {
                 StreamTag::Reso => {
-                    // Conversion from 16.16 fixed point integer to a float.
-                    let _ = canvas
-                        .dots_per_inch
-                        .insert(reader.read_u32()? as f32 / 65536f32);
+                    let _ = canvas.resolution.insert(reader.read_u32()?);
 
                     let size_unit = reader.read_u16()?;
                     let size_unit = SizeUnit::new(size_unit)?;
@@ -134,4 +138,22 @@ impl Canvas {
 
         Ok(canvas)
     }
+
+    /// The [`Canvas::resolution`] as a float, in [`Canvas::resolution_unit`].
+    #[allow(clippy::cast_precision_loss)]
+    pub fn resolution(&self) -> Option<f32> {
+        // Conversion from 16.16 fixed point integer to a float.
+        self.resolution
+            .map(|resolution| resolution as f32 / 65536f32)
+    }
+
+    /// The [`Canvas::resolution`] in pixels per inch, converting it from pixels per centimeter if
+    /// needed.
+    pub fn dots_per_inch(&self) -> Option<f32> {
+        let resolution = self.resolution()?;
+        Some(match self.resolution_unit {
+            Some(ResolutionUnit::PixelsCm) => resolution * 2.54,
+            Some(ResolutionUnit::PixelsInch) | None => resolution,
+        })
+    }
 }
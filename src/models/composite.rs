@@ -0,0 +1,64 @@
+#[cfg(feature = "png")]
+use crate::internals::image::PngImage;
+#[cfg(feature = "webp")]
+use crate::internals::image::WebpImage;
+#[cfg(any(feature = "png", feature = "webp"))]
+use std::io;
+
+/// The flattened image of all the layers of a sai file; see [`Sai::composite`].
+///
+/// [`Sai::composite`]: crate::Sai::composite
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompositeImage {
+    /// Width of the canvas.
+    pub width: u32,
+    /// Height of the canvas.
+    pub height: u32,
+    /// Pixels in RGBA color model ( `straight` alpha ).
+    pub data: Vec<u8>,
+}
+
+impl CompositeImage {
+    /// Gets a png image from the underlying `CompositeImage` pixels.
+    ///
+    /// # Errors
+    ///
+    /// - If it wasn't able to save the image.
+    #[cfg(feature = "png")]
+    pub fn to_png<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let png = PngImage {
+            width: self.width,
+            height: self.height,
+            ..Default::default()
+        };
+        png.save(&self.data, path)
+    }
+
+    /// Saves the underlying `CompositeImage` pixels as a webp image.
+    ///
+    /// If `quality` is [`None`] the image is encoded losslessly; otherwise, it is encoded lossy
+    /// with the given `quality` ( from `0.0` to `100.0` ).
+    ///
+    /// # Errors
+    ///
+    /// - If `quality` is not between `0.0` and `100.0` ([`io::ErrorKind::InvalidInput`]).
+    ///
+    /// - If it wasn't able to encode, or save the image.
+    #[cfg(feature = "webp")]
+    pub fn to_webp<P>(&self, path: P, quality: Option<f32>) -> io::Result<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let webp = WebpImage {
+            width: self.width,
+            height: self.height,
+            lossless: quality.is_none(),
+            quality: quality.unwrap_or(WebpImage::default().quality),
+            ..Default::default()
+        };
+        webp.save(&self.data, path)
+    }
+}
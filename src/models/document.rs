@@ -1,5 +1,8 @@
 use crate::internals::{binreader::BinReader, time};
-use std::io::{self, Read};
+use std::{
+    io::{self, Read},
+    time::SystemTime,
+};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Document {
@@ -22,6 +25,11 @@ pub struct Document {
 }
 
 impl Document {
+    /// Reads a `Document` from the bytes of the `.xxxxxxxxxxxxxxxx` file.
+    ///
+    /// # Errors
+    ///
+    /// - If `reader` doesn't hold a valid document file.
     pub fn from_reader<R>(reader: &mut R) -> io::Result<Self>
     where
         R: Read,
@@ -51,4 +59,34 @@ impl Document {
             machine_hash: reader.read_u64()?,
         })
     }
+
+    /// Same as [`date_created`], but as a [`SystemTime`].
+    ///
+    /// [`date_created`]: Document::date_created
+    pub fn created_time(&self) -> SystemTime {
+        time::unixtime_to_system_time(self.date_created)
+    }
+
+    /// Same as [`date_modified`], but as a [`SystemTime`].
+    ///
+    /// [`date_modified`]: Document::date_modified
+    pub fn modified_time(&self) -> SystemTime {
+        time::unixtime_to_system_time(self.date_modified)
+    }
+
+    /// Same as [`date_created`], but as a [`chrono::DateTime`].
+    ///
+    /// [`date_created`]: Document::date_created
+    #[cfg(feature = "chrono")]
+    pub fn created_datetime(&self) -> chrono::DateTime<chrono::Utc> {
+        time::unixtime_to_datetime(self.date_created)
+    }
+
+    /// Same as [`date_modified`], but as a [`chrono::DateTime`].
+    ///
+    /// [`date_modified`]: Document::date_modified
+    #[cfg(feature = "chrono")]
+    pub fn modified_datetime(&self) -> chrono::DateTime<chrono::Utc> {
+        time::unixtime_to_datetime(self.date_modified)
+    }
 }
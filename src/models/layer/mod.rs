@@ -2,20 +2,29 @@ mod table;
 
 pub use self::table::{LayerRef, LayerTable};
 
+#[cfg(any(feature = "png", feature = "webp"))]
+use crate::internals::image::ColorType;
+#[cfg(feature = "webp")]
+use crate::internals::image::WebpImage;
+#[cfg(feature = "png")]
+use crate::internals::image::{BitDepth, PngImage};
 use crate::{
     cipher::PAGE_SIZE,
-    internals::{binreader::BinReader, image::PngImage},
-    pixel_ops::premultiplied_to_straight,
+    internals::binreader::BinReader,
+    pixel_ops::{self, premultiplied_to_straight, AlphaMode, ChannelOrder},
 };
 use itertools::Itertools;
 use std::{
+    borrow::Cow,
     cmp::Ordering,
     ffi::CStr,
+    fmt,
     io::{self, Read},
+    str::FromStr,
 };
 
 #[repr(u16)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum LayerKind {
     /// Canvas pseudo-layer.
     RootLayer = 0x00,
@@ -44,10 +53,61 @@ impl LayerKind {
             _ => return Err(io::ErrorKind::InvalidData.into()),
         })
     }
+
+    /// The name of this kind, as it is written by [`Display`], and read by [`FromStr`].
+    ///
+    /// [`Display`]: fmt::Display
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::RootLayer => "RootLayer",
+            Self::Regular => "Regular",
+            Self::_Unknown4 => "Unknown4",
+            Self::Linework => "Linework",
+            Self::Mask => "Mask",
+            Self::_Unknown7 => "Unknown7",
+            Self::Set => "Set",
+        }
+    }
+}
+
+impl fmt::Display for LayerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
+impl FromStr for LayerKind {
+    type Err = ParseLayerKindError;
+
+    /// Parses the [`LayerKind::name`] of a kind ( case sensitive ).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "RootLayer" => Self::RootLayer,
+            "Regular" => Self::Regular,
+            "Unknown4" => Self::_Unknown4,
+            "Linework" => Self::Linework,
+            "Mask" => Self::Mask,
+            "Unknown7" => Self::_Unknown7,
+            "Set" => Self::Set,
+            _ => return Err(ParseLayerKindError(s.to_owned())),
+        })
+    }
+}
+
+/// The error returned when a string is not the name of any [`LayerKind`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseLayerKindError(String);
+
+impl fmt::Display for ParseLayerKindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is not a valid layer kind", self.0)
+    }
+}
+
+impl std::error::Error for ParseLayerKindError {}
+
 #[repr(u32)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BlendingMode {
     PassThrough,
     Normal,
@@ -76,6 +136,69 @@ impl BlendingMode {
             _ => return Err(io::ErrorKind::InvalidData.into()),
         })
     }
+
+    /// The inverse of [`BlendingMode::new`].
+    fn to_bytes(self) -> [u8; 4] {
+        let mut buf = *match self {
+            Self::PassThrough => b"pass",
+            Self::Normal => b"norm",
+            Self::Multiply => b"mul ",
+            Self::Screen => b"scrn",
+            Self::Overlay => b"over",
+            Self::Luminosity => b"add ",
+            Self::Shade => b"sub ",
+            Self::LumiShade => b"adsb",
+            Self::Binary => b"cbin",
+        };
+        buf.reverse();
+        buf
+    }
+
+    /// Whether or not `target` has a blending mode that gives the same result as this one.
+    ///
+    /// When it doesn't, an export to `target` can only use the closest approximation.
+    ///
+    /// NOTE: `Luminosity` is SAI's `add` (linear dodge), which only matches the Psd blend mode;
+    /// `svg:plus` and `plus-lighter` are compositing operators instead, and they don't account for
+    /// the alpha of both layers the same way. `Shade` (linear burn) only exists on Psd, while
+    /// `LumiShade` and `Binary` don't have an equivalent on any target.
+    pub const fn interop_is_exact(self, target: InteropTarget) -> bool {
+        match self {
+            Self::PassThrough | Self::Normal | Self::Multiply | Self::Screen | Self::Overlay => {
+                true
+            }
+            Self::Luminosity | Self::Shade => matches!(target, InteropTarget::Psd),
+            Self::LumiShade | Self::Binary => false,
+        }
+    }
+
+    /// Gets the `CSS` `mix-blend-mode` keyword that matches this mode, or [`None`] if there
+    /// isn't one.
+    ///
+    /// `Luminosity` maps to `plus-lighter` instead of `luminosity`, because SAI's `Luminosity`
+    /// adds both layers together; CSS `luminosity` is a whole different (non-separable) mode.
+    pub const fn to_css(self) -> Option<&'static str> {
+        Some(match self {
+            // Elements are not isolated by default, so their children already blend through.
+            Self::PassThrough | Self::Normal => "normal",
+            Self::Multiply => "multiply",
+            Self::Screen => "screen",
+            Self::Overlay => "overlay",
+            Self::Luminosity => "plus-lighter",
+            Self::Shade | Self::LumiShade | Self::Binary => return None,
+        })
+    }
+}
+
+/// Formats that layers can be exported to; see [`BlendingMode::interop_is_exact`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InteropTarget {
+    /// Adobe `Photoshop` documents.
+    Psd,
+    /// `OpenRaster` documents.
+    Ora,
+    /// `CSS` `mix-blend-mode` property.
+    Css,
 }
 
 /// Rectangular bounds
@@ -83,17 +206,77 @@ impl BlendingMode {
 /// Can be off-canvas or larger than canvas if the user moves the layer outside
 /// of the `canvas window` without cropping; similar to `Photoshop`, 0:0 is
 /// top-left corner of image.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct LayerBounds {
     pub x: i32,
     pub y: i32,
-    /// Always rounded to nearest multiple of 32.
+    /// Always rounded to nearest multiple of 32 on [`Layer::bounds`].
     pub width: u32,
-    /// Always rounded to nearest multiple of 32.
+    /// Always rounded to nearest multiple of 32 on [`Layer::bounds`].
     pub height: u32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The decoded [`Layer::data`] of a layer, along with the format of its pixels; see
+/// [`Sai::layer_data`].
+///
+/// [`Sai::layer_data`]: crate::Sai::layer_data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayerData {
+    /// The pixels, row by row.
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// [`None`] for [`LayerKind::Mask`] layers, since they don't have an alpha channel.
+    pub alpha_mode: Option<AlphaMode>,
+    /// The amount of channels ( 1 byte each ) of every pixel: `4` for RGBA pixels, and `1` for
+    /// the grayscale pixels of [`LayerKind::Mask`] layers.
+    pub channels: u8,
+}
+
+impl LayerBounds {
+    /// Whether or not the `(x, y)` point is inside of these bounds; the right and bottom edges are
+    /// not included.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (x, y) = (i64::from(x), i64::from(y));
+        let (left, top) = (i64::from(self.x), i64::from(self.y));
+
+        (left..left + i64::from(self.width)).contains(&x)
+            && (top..top + i64::from(self.height)).contains(&y)
+    }
+
+    /// Whether or not these bounds and `other` have any point in common; touching edges don't
+    /// count.
+    pub fn overlaps(&self, other: &LayerBounds) -> bool {
+        let ranges = |bounds: &LayerBounds| {
+            let (x, y) = (i64::from(bounds.x), i64::from(bounds.y));
+            (
+                x..x + i64::from(bounds.width),
+                y..y + i64::from(bounds.height),
+            )
+        };
+        let ((x, y), (other_x, other_y)) = (ranges(self), ranges(other));
+
+        x.start < other_x.end
+            && other_x.start < x.end
+            && y.start < other_y.end
+            && other_y.start < y.end
+    }
+}
+
+/// The position of a 32x32 tile within the [`LayerBounds`] of its layer, counted in tiles; i.e:
+/// `TileCoord { x: 1, y: 0 }` covers the pixels `32..64` of the first 32 rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileCoord {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileCoord {
+    /// The width and height ( in pixels ) of a tile.
+    pub const SIZE: u32 = 32;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TextureName {
     WatercolorA,
     WatercolorB,
@@ -113,7 +296,7 @@ impl TextureName {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Texture {
     /// Name of the overlay-texture assigned to a layer. i.e: `Watercolor A`.
     pub name: TextureName,
@@ -133,7 +316,7 @@ impl Default for Texture {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Effect {
     /// Value ranging from `0` to `100`.
     pub opacity: u8,
@@ -177,7 +360,54 @@ impl TryFrom<[u8; 4]> for StreamTag {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Caps the amount of memory that decompressing [`Layer::data`] can take.
+///
+/// `max_total_bytes` is shared between all the layers that are decompressed with the same limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// The maximum amount of pixels (`width * height`) that a single layer can have.
+    pub max_pixels: u64,
+    /// The maximum amount of bytes that all the decompressed layers can take together.
+    pub max_total_bytes: u64,
+}
+
+impl DecodeLimits {
+    /// Takes the bytes needed by a `width * height` layer ( of `pixel_size` bytes per pixel ) out of
+    /// `max_total_bytes`.
+    fn take(&mut self, width: u32, height: u32, pixel_size: usize) -> io::Result<()> {
+        let pixels = u64::from(width) * u64::from(height);
+        if pixels > self.max_pixels {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!("layer of {width}x{height} pixels goes over the pixels limit"),
+            ));
+        }
+
+        self.max_total_bytes = self
+            .max_total_bytes
+            .checked_sub(pixels * pixel_size as u64)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::OutOfMemory,
+                    "decompressed layers go over the bytes limit",
+                )
+            })?;
+
+        Ok(())
+    }
+}
+
+impl Default for DecodeLimits {
+    /// No limits at all.
+    fn default() -> Self {
+        Self {
+            max_pixels: u64::MAX,
+            max_total_bytes: u64::MAX,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Layer {
     pub kind: LayerKind,
     /// The identifier of the layer.
@@ -197,8 +427,11 @@ pub struct Layer {
 
     /// The name of the layer.
     ///
-    /// It is always safe to [`unwrap`] if [`LayerKind::Regular`].
+    /// `None` if the `name` stream is missing, or if it isn't valid utf-8; the layers gotten
+    /// through [`Sai`] fall back to the name of their file instead ( i.e: `00000002` ), so it is
+    /// always safe to [`unwrap`] on them.
     ///
+    /// [`Sai`]: crate::Sai
     /// [`unwrap`]: Option::unwrap
     pub name: Option<String>,
     /// If this layer is a child of a [`LayerKind::Set`], this will be the layer id of that
@@ -216,19 +449,114 @@ pub struct Layer {
     ///
     /// If the layer is [`LayerKind::Set`], there is no additional data. If the layer is
     /// [`LayerKind::Regular`] then data will hold pixels in the RGBA color model with
-    /// pre-multiplied alpha.
+    /// pre-multiplied alpha. If the layer is [`LayerKind::Mask`] then data will hold grayscale
+    /// pixels ( 1 byte each ), where `0` hides the pixels of the parent layer.
     ///
     /// For now, others [`LayerKind`]s will not include their additional data.
     pub data: Option<Vec<u8>>,
 }
 
 impl Layer {
+    /// The size (on bytes) of the header that comes before the streams of a layer file.
+    pub(crate) const HEADER_SIZE: usize = 37;
+
+    /// # Errors
+    ///
+    /// - If `reader` doesn't hold a valid layer file.
     pub fn from_reader<R>(reader: &mut R, decompress_data: bool) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let mut limits = DecodeLimits::default();
+        Self::from_reader_limited(reader, decompress_data.then_some(&mut limits))
+    }
+
+    /// Same as [`Layer::from_reader`], but the data is only decompressed if `limits` is `Some`,
+    /// and if doing so doesn't go over them.
+    pub(crate) fn from_reader_limited<R>(
+        reader: &mut R,
+        limits: Option<&mut DecodeLimits>,
+    ) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let mut reader = BinReader::new(reader);
+        let mut layer = Self::read_properties(&mut reader)?;
+        let (kind, bounds) = (layer.kind, layer.bounds);
+
+        if let Some(limits) =
+            limits.filter(|_| matches!(kind, LayerKind::Regular | LayerKind::Mask))
+        {
+            limits.take(bounds.width, bounds.height, layer.pixel_size())?;
+
+            let dimensions = (bounds.width as usize, bounds.height as usize);
+            let data = if kind == LayerKind::Mask {
+                decompress_mask(&mut reader, dimensions)?
+            } else {
+                decompress(&mut reader, dimensions)?
+            };
+            let _ = layer.data.insert(data);
+        }
+
+        Ok(layer)
+    }
+
+    /// Same as [`Layer::from_reader`] ( without decompressing `data` ), but gives every non-empty
+    /// tile of a [`LayerKind::Regular`] to `f` as soon as it is decompressed, as pre-multiplied
+    /// RGBA pixels ( row by row ).
+    ///
+    /// Any other [`LayerKind`] has no tiles.
+    pub(crate) fn from_reader_tiles<R, F>(reader: &mut R, mut f: F) -> io::Result<Self>
+    where
+        R: Read,
+        F: FnMut(&Layer, TileCoord, &[u8; PAGE_SIZE]),
+    {
+        let mut reader = BinReader::new(reader);
+        let layer = Self::read_properties(&mut reader)?;
+
+        if layer.kind == LayerKind::Regular {
+            let dimensions = (layer.bounds.width as usize, layer.bounds.height as usize);
+            decompress_tiles(&mut reader, dimensions, |coord, tile| {
+                f(&layer, coord, tile);
+            })?;
+        }
+
+        Ok(layer)
+    }
+
+    /// Same as [`Layer::from_reader`] ( without decompressing `data` ), but also gives the pixels
+    /// of a [`LayerKind::Mask`] with their original precision, scaled from `0..=0x4000` to the
+    /// whole range of an `u16` ( instead of the lossy `0..=255` of [`Layer::data`] ).
+    ///
+    /// Returns [`None`] if the layer is not a [`LayerKind::Mask`].
+    #[cfg(feature = "png")]
+    pub(crate) fn mask_from_reader16<R>(reader: &mut R) -> io::Result<Option<(Self, Vec<u16>)>>
     where
         R: Read,
     {
         let mut reader = BinReader::new(reader);
+        let layer = Self::read_properties(&mut reader)?;
+
+        if layer.kind != LayerKind::Mask {
+            return Ok(None);
+        }
+
+        let dimensions = (layer.bounds.width as usize, layer.bounds.height as usize);
+        #[allow(clippy::cast_possible_truncation)]
+        let pixels = decompress_mask_with(&mut reader, dimensions, |value| {
+            (u32::from(value.min(0x4000)) * u32::from(u16::MAX) / 0x4000) as u16
+        })?;
 
+        Ok(Some((layer, pixels)))
+    }
+
+    /// Reads the header and the streams of a layer file, leaving `reader` at the start of its
+    /// data.
+    #[allow(clippy::cast_possible_truncation)]
+    fn read_properties<R>(reader: &mut BinReader<R>) -> io::Result<Self>
+    where
+        R: Read,
+    {
         let kind = reader.read_u32()?;
         #[allow(clippy::cast_lossless)]
         let kind = LayerKind::new(kind as u16)?;
@@ -270,23 +598,24 @@ impl Layer {
 
         while let Some((tag, size)) = reader.read_stream_header().transpose()? {
             let Some(tag) = tag else {
-                reader.skip(size as usize)?;
+                reader.skip_stream_body(size)?;
                 continue;
             };
             match tag {
                 StreamTag::Name => {
-                    let name = reader.read_array::<256>()?;
-                    let name = CStr::from_bytes_until_nul(&name)
-                        .map_err(|_| io::ErrorKind::InvalidData)?
-                        .to_string_lossy()
-                        .into_owned();
-                    let _ = layer.name.insert(name);
+                    // NOTE: A name that can't be decoded is left as `None`, instead of failing to
+                    // read the whole layer; see [`Layer::name`].
+                    let name = reader.read_stream_body(size)?;
+                    layer.name = CStr::from_bytes_until_nul(&name)
+                        .ok()
+                        .and_then(|name| name.to_str().ok())
+                        .map(str::to_owned);
                 }
                 StreamTag::Pfid => _ = layer.parent_set.insert(reader.read_u32()?),
                 StreamTag::Plid => _ = layer.parent_layer.insert(reader.read_u32()?),
                 StreamTag::Fopn => _ = layer.open.insert(reader.read_bool()?),
                 StreamTag::Texn => {
-                    let buf = reader.read_array::<64>()?;
+                    let buf = reader.read_stream_body(size)?;
                     let name = String::from_utf8_lossy(&buf);
                     let name = TextureName::new(name.trim_end_matches('\0'))?;
 
@@ -300,26 +629,299 @@ impl Layer {
                     if let Some(ref mut texture) = layer.texture {
                         texture.scale = scale;
                         texture.opacity = opacity;
-                    };
+                    }
                 }
                 StreamTag::Peff => {
                     let enabled = reader.read_bool()?;
                     let opacity = reader.read_u8()?;
                     let width = reader.read_u8()?;
+                    // NOTE(rev-eng): `peff` has always been 3 bytes long on the files that
+                    // were checked, so the fringe color is not stored here ( if stored at all ).
+                    // Skip anything else, instead of corrupting the next stream readings.
+                    reader.skip_stream_body(size.saturating_sub(3))?;
 
                     if enabled {
                         let _ = layer.effect.insert(Effect { opacity, width });
-                    };
+                    }
                 }
             }
         }
 
-        if decompress_data && matches!(kind, LayerKind::Regular) {
-            let dimensions = (bounds.width as usize, bounds.height as usize);
-            let _ = layer.data.insert(decompress(&mut reader, dimensions)?);
+        Ok(layer)
+    }
+
+    /// Overwrites the properties that can be modified through [`LayerMut`] on `bytes`, which
+    /// should be the start of the file that this layer was read from.
+    ///
+    /// The `name` stream is overwritten in place, so the new name (plus its nul terminator) needs
+    /// to fit on it.
+    pub(crate) fn write_header(&self, bytes: &mut [u8]) -> io::Result<()> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        bytes[28] = self.opacity;
+        bytes[29] = u8::from(self.visible);
+        bytes[33..Self::HEADER_SIZE].copy_from_slice(&self.blending_mode.to_bytes());
+
+        let Some(ref name) = self.name else {
+            return Ok(());
         };
 
-        Ok(layer)
+        let mut offset = Self::HEADER_SIZE;
+        loop {
+            let header = bytes
+                .get(offset..offset + 8)
+                .ok_or(io::ErrorKind::UnexpectedEof)?;
+            let (tag, size) = header.split_at(4);
+            let size = u32::from_le_bytes(size.try_into().expect("4 bytes")) as usize;
+            if tag == [0; 4] {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "layer doesn't have a name stream",
+                ));
+            }
+
+            if tag == *b"eman" {
+                let body = bytes
+                    .get_mut(offset + 8..offset + 8 + size)
+                    .ok_or(io::ErrorKind::UnexpectedEof)?;
+                if name.len() >= body.len() || name.contains('\0') {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("name can't be stored on {size} bytes"),
+                    ));
+                }
+
+                body.fill(0);
+                body[..name.len()].copy_from_slice(name.as_bytes());
+                return Ok(());
+            }
+
+            offset += 8 + size;
+        }
+    }
+
+    /// The position (in canvas coordinates) of the top-left corner of [`Layer::data`].
+    ///
+    /// NOTE(rev-eng): The decompressed data always starts 8 pixels up and to the left of
+    /// [`Layer::bounds`]; found by comparing the layers against the png exports on `/res`.
+    pub const fn data_origin(&self) -> (i32, i32) {
+        (self.bounds.x - 8, self.bounds.y - 8)
+    }
+
+    /// Gets the smallest rectangle (in canvas coordinates) that holds all the non-transparent
+    /// pixels of this layer.
+    ///
+    /// Returns [`None`] if the layer doesn't have [`Layer::data`], or if all its pixels are
+    /// transparent.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub fn content_bounds(&self) -> Option<LayerBounds> {
+        let data = self.data.as_ref()?;
+        let width = self.bounds.width as usize;
+        let pixel_size = self.pixel_size();
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+        for (idx, _) in data
+            .chunks_exact(pixel_size)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[pixel_size - 1] != 0)
+        {
+            let (x, y) = (idx % width, idx / width);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+
+        (min_x != usize::MAX).then(|| {
+            let (x, y) = self.data_origin();
+            LayerBounds {
+                x: x + min_x as i32,
+                y: y + min_y as i32,
+                width: (max_x - min_x + 1) as u32,
+                height: (max_y - min_y + 1) as u32,
+            }
+        })
+    }
+
+    /// The amount of bytes that each pixel of [`Layer::data`] takes.
+    fn pixel_size(&self) -> usize {
+        if self.kind == LayerKind::Mask {
+            1
+        } else {
+            4
+        }
+    }
+
+    /// Converts [`Layer::data`] (if any) from `pre-multiplied alpha` to `straight` color format,
+    /// without allocating a new buffer.
+    ///
+    /// Keep in mind that the rest of the crate expects `data` to be `pre-multiplied`; use
+    /// [`premultiply_in_place`] to convert it back.
+    ///
+    /// Does nothing on [`LayerKind::Mask`] layers, since they don't have an alpha channel.
+    ///
+    /// [`premultiply_in_place`]: Layer::premultiply_in_place
+    pub fn unpremultiply_in_place(&mut self) {
+        if self.kind == LayerKind::Mask {
+            return;
+        }
+        if let Some(ref mut data) = self.data {
+            pixel_ops::premultiplied_to_straight_in_place(data);
+        }
+    }
+
+    /// Converts [`Layer::data`] (if any) from `straight` to `pre-multiplied alpha` color format,
+    /// without allocating a new buffer.
+    ///
+    /// Does nothing on [`LayerKind::Mask`] layers, since they don't have an alpha channel.
+    pub fn premultiply_in_place(&mut self) {
+        if self.kind == LayerKind::Mask {
+            return;
+        }
+        if let Some(ref mut data) = self.data {
+            pixel_ops::straight_to_premultiplied_in_place(data);
+        }
+    }
+
+    /// Gets [`Layer::data`] (if any) with the channels of every pixel in the given `order`.
+    ///
+    /// Only [`ChannelOrder::Bgra`] needs to copy the data; [`LayerKind::Mask`] layers are
+    /// returned as is, since they only have one channel.
+    pub fn data_with_order(&self, order: ChannelOrder) -> Option<Cow<'_, [u8]>> {
+        let data = self.data.as_deref()?;
+        if order == ChannelOrder::Rgba || self.kind == LayerKind::Mask {
+            return Some(Cow::Borrowed(data));
+        }
+
+        let mut data = data.to_vec();
+        pixel_ops::swap_red_blue_in_place(&mut data);
+        Some(Cow::Owned(data))
+    }
+
+    /// Mirrors [`Layer::data`] (if any) from left to right, without moving the layer.
+    pub fn flip_horizontal(&mut self) {
+        let pixel_size = self.pixel_size();
+        let width = self.bounds.width as usize;
+        if let Some(ref mut data) = self.data {
+            for row in data.chunks_exact_mut(width * pixel_size) {
+                reverse_pixels(row, pixel_size);
+            }
+        }
+    }
+
+    /// Mirrors [`Layer::data`] (if any) from top to bottom, without moving the layer.
+    pub fn flip_vertical(&mut self) {
+        let stride = self.bounds.width as usize * self.pixel_size();
+        if let Some(ref mut data) = self.data {
+            let height = data.len() / stride;
+            for y in 0..height / 2 {
+                let (top, bottom) = data.split_at_mut((height - y - 1) * stride);
+                top[y * stride..][..stride].swap_with_slice(&mut bottom[..stride]);
+            }
+        }
+    }
+
+    /// Rotates [`Layer::data`] (if any) by 180 degrees, without moving the layer.
+    ///
+    /// Same as calling both [`Layer::flip_horizontal`] and [`Layer::flip_vertical`].
+    pub fn rotate_180(&mut self) {
+        let pixel_size = self.pixel_size();
+        if let Some(ref mut data) = self.data {
+            reverse_pixels(data, pixel_size);
+        }
+    }
+
+    /// Compresses [`Layer::data`] (if any) back into the format that SAI uses to store it at the
+    /// end of a layer file.
+    ///
+    /// For now, [`LayerKind::Mask`] data can't be compressed, so [`None`] is returned instead.
+    pub fn compress_data(&self) -> Option<Vec<u8>> {
+        let data = self
+            .data
+            .as_ref()
+            .filter(|_| self.kind != LayerKind::Mask)?;
+        Some(compress(
+            data,
+            (self.bounds.width as usize, self.bounds.height as usize),
+        ))
+    }
+
+    /// Gets the [`content_bounds`] of this layer, alongside a copy of the [`Layer::data`] pixels
+    /// that are inside of them.
+    ///
+    /// [`content_bounds`]: Layer::content_bounds
+    pub(crate) fn cropped_content(&self) -> Option<(LayerBounds, Vec<u8>)> {
+        let content = self.content_bounds()?;
+        Some((content, self.crop(content)?))
+    }
+
+    /// Gets the part of the [`content_bounds`] that is inside of a canvas of `(width, height)`,
+    /// alongside a copy of the [`Layer::data`] pixels found there; i.e: only the on-canvas part
+    /// of a layer that was dragged partly off-canvas (negative [`Layer::bounds`]).
+    ///
+    /// Returns [`None`] if there are no non-transparent pixels inside of the canvas.
+    ///
+    /// [`content_bounds`]: Layer::content_bounds
+    #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+    pub fn crop_to_canvas(&self, (width, height): (u32, u32)) -> Option<(LayerBounds, Vec<u8>)> {
+        let content = self.content_bounds()?;
+
+        let (x, y) = (content.x.max(0), content.y.max(0));
+        let right = (content.x + content.width as i32).min(width as i32);
+        let bottom = (content.y + content.height as i32).min(height as i32);
+        if x >= right || y >= bottom {
+            return None;
+        }
+
+        let rect = LayerBounds {
+            x,
+            y,
+            width: (right - x) as u32,
+            height: (bottom - y) as u32,
+        };
+        Some((rect, self.crop(rect)?))
+    }
+
+    /// Copies the pixels of [`Layer::data`] found inside of `rect` (in canvas coordinates).
+    ///
+    /// For now, [`LayerKind::Mask`] data can't be cropped.
+    #[allow(clippy::cast_sign_loss)]
+    fn crop(&self, rect: LayerBounds) -> Option<Vec<u8>> {
+        let data = self
+            .data
+            .as_ref()
+            .filter(|_| self.kind != LayerKind::Mask)?;
+
+        let (origin_x, origin_y) = self.data_origin();
+        Some(pixel_ops::crop_rgba(
+            data,
+            self.bounds.width,
+            (
+                (rect.x - origin_x) as u32,
+                (rect.y - origin_y) as u32,
+                rect.width,
+                rect.height,
+            ),
+        ))
+    }
+
+    /// Gets the `width`, `height` and pixels of [`Layer::data`] (if any), without needing any
+    /// image encoding feature.
+    ///
+    /// The pixels are stored row by row, from the top-left corner. [`LayerKind::Regular`] pixels
+    /// are 4 bytes each ( RGBA, with `straight` alpha ), and [`LayerKind::Mask`] pixels are 1
+    /// byte each ( grayscale ).
+    pub fn to_rgba(&self) -> Option<(u32, u32, Vec<u8>)> {
+        let data = self.data.as_ref()?;
+        let pixels = if self.kind == LayerKind::Mask {
+            data.clone()
+        } else {
+            premultiplied_to_straight(data)
+        };
+
+        Some((self.bounds.width, self.bounds.height, pixels))
     }
 
     /// Gets a png image from the underlying layer data.
@@ -349,74 +951,364 @@ impl Layer {
     ///
     /// # Panics
     ///
-    /// - If invoked with a layer with a kind other than [`LayerKind::Regular`].
-
+    /// - If invoked with a layer with a kind other than [`LayerKind::Regular`] or
+    ///   [`LayerKind::Mask`] ( saved as a grayscale image ).
+    ///
+    /// # Precision
+    ///
+    /// SAI stores mask pixels as values from `0` to `0x4000`, which are scaled down to 8 bits on
+    /// [`Layer::data`]; use [`Sai::mask_to_png16`] to keep all of them.
+    ///
+    /// [`Sai::mask_to_png16`]: crate::Sai::mask_to_png16
     // TODO(Unavailable): size_hint: Option<SizeHint>
     #[cfg(feature = "png")]
     pub fn to_png<P>(&self, path: Option<P>) -> io::Result<()>
     where
         P: AsRef<std::path::Path>,
     {
-        if let Some(ref image_data) = self.data {
+        if let Some((width, height, pixels)) = self.to_rgba() {
             let png = PngImage {
-                width: self.bounds.width,
-                height: self.bounds.height,
+                color: if self.kind == LayerKind::Mask {
+                    ColorType::Grayscale
+                } else {
+                    ColorType::Rgba
+                },
+                depth: BitDepth::Eight,
+                width,
+                height,
                 ..Default::default()
             };
+            return png.save(&pixels, self.image_path(path, "png"));
+        }
+
+        panic!("For now, saire can only decompress LayerKind::Regular and LayerKind::Mask data.");
+    }
 
-            let path = path.map_or_else(
-                || {
-                    std::path::PathBuf::from(format!(
-                        "{:0>8x}-{}.png",
-                        self.id,
-                        self.name.as_ref().unwrap()
-                    ))
+    /// Same as [`Layer::to_png`], but saves a webp image ( `./{id}-{name}.webp` if `path` is
+    /// `None` ).
+    ///
+    /// If `quality` is [`None`] the image is encoded losslessly; otherwise, it is encoded lossy
+    /// with the given `quality` ( from `0.0` to `100.0` ). Lossless webp images are usually way
+    /// smaller than png ones for layers, which tend to have big transparent areas.
+    ///
+    /// # Errors
+    ///
+    /// - If `quality` is not between `0.0` and `100.0` ([`io::ErrorKind::InvalidInput`]).
+    ///
+    /// - If it wasn't able to encode, or save the image.
+    ///
+    /// # Panics
+    ///
+    /// - If invoked with a layer with a kind other than [`LayerKind::Regular`] or
+    ///   [`LayerKind::Mask`] ( saved as an opaque grayscale image ).
+    #[cfg(feature = "webp")]
+    pub fn to_webp<P>(&self, path: Option<P>, quality: Option<f32>) -> io::Result<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        if let Some((width, height, pixels)) = self.to_rgba() {
+            let webp = WebpImage {
+                color: if self.kind == LayerKind::Mask {
+                    ColorType::Grayscale
+                } else {
+                    ColorType::Rgba
                 },
-                |path| path.as_ref().to_path_buf(),
-            );
+                width,
+                height,
+                lossless: quality.is_none(),
+                quality: quality.unwrap_or(WebpImage::default().quality),
+            };
+            return webp.save(&pixels, self.image_path(path, "webp"));
+        }
+
+        panic!("For now, saire can only decompress LayerKind::Regular and LayerKind::Mask data.");
+    }
+
+    /// Same as [`Layer::to_png`], but saves a [`LayerKind::Mask`] as a 1-bit grayscale image,
+    /// where every pixel that is at least `threshold` becomes white, and the rest black.
+    ///
+    /// Hard masks lose nothing by this, while taking a fraction of the space.
+    ///
+    /// # Errors
+    ///
+    /// - If it wasn't able to save the image.
+    ///
+    /// # Panics
+    ///
+    /// - If invoked with a layer with a kind other than [`LayerKind::Mask`].
+    #[cfg(feature = "png")]
+    pub fn to_binary_png<P>(&self, path: Option<P>, threshold: u8) -> io::Result<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        match self.data {
+            Some(ref image_data) if self.kind == LayerKind::Mask => {
+                let png = PngImage {
+                    color: ColorType::Grayscale,
+                    depth: BitDepth::One,
+                    width: self.bounds.width,
+                    height: self.bounds.height,
+                    ..Default::default()
+                };
+                let thresholded: Vec<u8> = image_data
+                    .iter()
+                    .map(|&pixel| u8::from(pixel >= threshold))
+                    .collect();
 
-            return png.save(&premultiplied_to_straight(image_data), path);
+                png.save(&thresholded, self.image_path(path, "png"))
+            }
+            _ => panic!("Only LayerKind::Mask layers can be saved as binary images."),
         }
+    }
 
-        panic!("For now, saire can only decompress LayerKind::Regular data.");
+    /// Either `path`, or `./{id}-{name}.{extension}` if `None`.
+    #[cfg(any(feature = "png", feature = "webp"))]
+    pub(crate) fn image_path<P>(&self, path: Option<P>, extension: &str) -> std::path::PathBuf
+    where
+        P: AsRef<std::path::Path>,
+    {
+        path.map_or_else(
+            || {
+                std::path::PathBuf::from(format!(
+                    "{:0>8x}-{}.{extension}",
+                    self.id,
+                    self.name.as_ref().unwrap()
+                ))
+            },
+            |path| path.as_ref().to_path_buf(),
+        )
     }
 }
 
-fn rle_decompress_stride(dst: &mut [u8], src: &[u8]) {
-    const STRIDE: usize = std::mem::size_of::<u32>();
-    const STRIDE_COUNT: usize = PAGE_SIZE / STRIDE;
+/// Reverses the order of the pixels ( of `pixel_size` bytes each ) of `pixels`.
+fn reverse_pixels(pixels: &mut [u8], pixel_size: usize) {
+    let len = pixels.len() / pixel_size;
+    for idx in 0..len / 2 {
+        let (left, right) = pixels.split_at_mut((len - idx - 1) * pixel_size);
+        left[idx * pixel_size..][..pixel_size].swap_with_slice(&mut right[..pixel_size]);
+    }
+}
+
+/// A [`Layer`] whose properties can be modified; see [`Sai::edit_layers`].
+///
+/// [`Layer::data`] is never decompressed.
+///
+/// [`Sai::edit_layers`]: crate::Sai::edit_layers
+#[derive(Clone, Debug)]
+pub struct LayerMut {
+    layer: Layer,
+    original: Layer,
+}
 
-    let mut src = src.iter();
-    let mut dst = dst.iter_mut().step_by(STRIDE);
-    let mut src = || src.next().expect("src has items");
-    let mut dst = || dst.next().expect("dst has items");
+impl LayerMut {
+    pub(crate) fn new(layer: Layer) -> Self {
+        Self {
+            original: layer.clone(),
+            layer,
+        }
+    }
 
-    let mut read = 0;
-    while read < STRIDE_COUNT {
-        let len = *src() as usize;
+    /// Whether or not any of the properties was modified.
+    pub(crate) fn is_modified(&self) -> bool {
+        self.layer != self.original
+    }
 
-        read += match len.cmp(&128) {
+    /// Gets the layer, with the properties modified so far.
+    #[inline]
+    pub const fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    #[inline]
+    pub fn set_visible(&mut self, visible: bool) {
+        self.layer.visible = visible;
+    }
+
+    /// Values bigger than `100` are clamped.
+    #[inline]
+    pub fn set_opacity(&mut self, opacity: u8) {
+        self.layer.opacity = opacity.min(100);
+    }
+
+    #[inline]
+    pub fn set_blending_mode(&mut self, blending_mode: BlendingMode) {
+        self.layer.blending_mode = blending_mode;
+    }
+
+    /// Only layers that already have a [`Layer::name`] can be renamed; the new name is limited
+    /// to the size of the old stream (255 bytes on the files that were checked).
+    #[inline]
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        let _ = self.layer.name.insert(name.into());
+    }
+}
+
+/// Decompresses the run-length encoded `src` into every `STRIDE`th byte of `dst`.
+fn rle_decompress_stride<const STRIDE: usize>(dst: &mut [u8], src: &[u8]) {
+    // The amount of values that are written to `dst`.
+    let count = dst.len().div_ceil(STRIDE);
+
+    let (mut read, mut written) = (0, 0);
+    while written < count {
+        let len = src[read] as usize;
+        read += 1;
+
+        match len.cmp(&128) {
             Ordering::Less => {
                 let len = len + 1;
-                (0..len).for_each(|_| *dst() = *src());
-                len
+                let literal = &src[read..][..len];
+                if STRIDE == 1 {
+                    dst[written..][..len].copy_from_slice(literal);
+                } else {
+                    for (dst, src) in dst[written * STRIDE..]
+                        .iter_mut()
+                        .step_by(STRIDE)
+                        .zip(literal)
+                    {
+                        *dst = *src;
+                    }
+                }
+                read += len;
+                written += len;
             }
             Ordering::Greater => {
                 let len = (len ^ 255) + 2;
-                let val = *src();
-                (0..len).for_each(|_| *dst() = val);
-                len
+                let val = src[read];
+                if STRIDE == 1 {
+                    dst[written..][..len].fill(val);
+                } else {
+                    dst[written * STRIDE..]
+                        .iter_mut()
+                        .step_by(STRIDE)
+                        .take(len)
+                        .for_each(|dst| *dst = val);
+                }
+                read += 1;
+                written += len;
             }
-            Ordering::Equal => 0,
+            Ordering::Equal => {}
         }
     }
 }
 
+/// The inverse of [`rle_decompress_stride`]; compresses every `STRIDE`th byte of `src`.
+#[allow(clippy::cast_possible_truncation)]
+fn rle_compress_stride(src: &[u8]) -> Vec<u8> {
+    const STRIDE: usize = std::mem::size_of::<u32>();
+    const STRIDE_COUNT: usize = PAGE_SIZE / STRIDE;
+    const MAX_LEN: usize = 128;
+
+    let src: Vec<_> = src.iter().step_by(STRIDE).take(STRIDE_COUNT).collect();
+    let mut dst = Vec::new();
+
+    let mut idx = 0;
+    while idx < src.len() {
+        let repeated = src[idx..]
+            .iter()
+            .take(MAX_LEN)
+            .take_while(|&&val| val == src[idx])
+            .count();
+
+        if repeated > 1 {
+            dst.push((257 - repeated) as u8);
+            dst.push(*src[idx]);
+            idx += repeated;
+        } else {
+            let start = idx;
+            while idx < src.len()
+                && idx - start < MAX_LEN
+                && src.get(idx + 1).is_none_or(|&next| next != src[idx])
+            {
+                idx += 1;
+            }
+
+            dst.push((idx - start - 1) as u8);
+            dst.extend(src[start..idx].iter().copied());
+        }
+    }
+
+    dst
+}
+
+/// The inverse of [`decompress`]; compresses RGBA `pixels` into the tile map and the tiles that
+/// SAI stores after the layer streams.
+#[allow(clippy::cast_possible_truncation)]
+fn compress(pixels: &[u8], (width, height): (usize, usize)) -> Vec<u8> {
+    const TILE_SIZE: usize = TileCoord::SIZE as usize;
+
+    let tile_map_height = height / TILE_SIZE;
+    let tile_map_width = width / TILE_SIZE;
+
+    let tile_at = |y: usize, x: usize| {
+        let mut tile = [0; PAGE_SIZE];
+        for (row, dst) in tile.chunks_exact_mut(TILE_SIZE * 4).enumerate() {
+            let offset = ((y * TILE_SIZE + row) * width + x * TILE_SIZE) * 4;
+            // Swaps RGBA -> BGRA
+            for (dst, src) in dst
+                .chunks_exact_mut(4)
+                .zip(pixels[offset..].chunks_exact(4))
+            {
+                dst.copy_from_slice(&[src[2], src[1], src[0], src[3]]);
+            }
+        }
+        tile
+    };
+
+    let tiles: Vec<_> = (0..tile_map_height)
+        .cartesian_product(0..tile_map_width)
+        .map(|(y, x)| Some(tile_at(y, x)).filter(|tile| tile.iter().any(|&byte| byte != 0)))
+        .collect();
+
+    let mut compressed: Vec<_> = tiles.iter().map(|tile| u8::from(tile.is_some())).collect();
+    for tile in tiles.iter().flatten() {
+        // NOTE(rev-eng): The purpose of the last 4 channels is unknown (they are skipped when
+        // decompressing). Most of the time they hold the BGRA channels divided by 2, so that is
+        // what is written.
+        let halved = tile.map(|byte| byte >> 1);
+
+        for channel in 0..8 {
+            let src = if channel < 4 { tile } else { &halved };
+            let stream = rle_compress_stride(&src[channel % 4..]);
+
+            compressed.extend((stream.len() as u16).to_le_bytes());
+            compressed.extend(stream);
+        }
+    }
+
+    compressed
+}
+
 fn decompress<R>(reader: &mut BinReader<R>, (width, height): (usize, usize)) -> io::Result<Vec<u8>>
 where
     R: Read,
 {
-    const TILE_SIZE: usize = 32;
+    const TILE_SIZE: usize = TileCoord::SIZE as usize;
+
+    let mut pixels = vec![0; width * height * 4];
+    decompress_tiles(reader, (width, height), |TileCoord { x, y }, tile| {
+        let (x, y) = (x as usize, y as usize);
+        for (row, src) in tile.chunks_exact(TILE_SIZE * 4).enumerate() {
+            // Offset of the first element of this row of the 32x32 tile within the final image.
+            let offset = (y * TILE_SIZE + row) * width + x * TILE_SIZE;
+            pixels[offset * 4..][..TILE_SIZE * 4].copy_from_slice(src);
+        }
+    })?;
+
+    Ok(pixels)
+}
+
+/// Decompresses the non-empty 32x32 tiles ( as pre-multiplied RGBA pixels, row by row ) of a
+/// [`LayerKind::Regular`], giving them to `f` as soon as each of them is ready.
+fn decompress_tiles<R, F>(
+    reader: &mut BinReader<R>,
+    (width, height): (usize, usize),
+    mut f: F,
+) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(TileCoord, &[u8; PAGE_SIZE]),
+{
+    const TILE_SIZE: usize = TileCoord::SIZE as usize;
 
     let tile_map_height = height / TILE_SIZE;
     let tile_map_width = width / TILE_SIZE;
@@ -426,15 +1318,12 @@ where
 
     // Prevents `tile_map` to be mutable.
     let tile_map = tile_map;
-    let mut pixels = vec![0; width * height * 4];
     let mut rle_dst = [0; PAGE_SIZE];
     let mut rle_src = [0; PAGE_SIZE / 2];
 
-    let pos2idx = |y, x, stride| y * stride + x;
-
     for (y, x) in (0..tile_map_height)
         .cartesian_product(0..tile_map_width)
-        .filter(|(y, x)| tile_map[pos2idx(*y, *x, tile_map_width)] != 0)
+        .filter(|(y, x)| tile_map[y * tile_map_width + x] != 0)
     {
         // Reads BGRA channels. Skip the next 4 ( unknown )
         for channel in 0..8 {
@@ -450,31 +1339,487 @@ where
             // realize that the `read` buffer is not used and remove any
             // copying involved.
             if channel < 4 {
-                rle_decompress_stride(&mut rle_dst[channel..], &rle_src);
+                rle_decompress_stride::<4>(&mut rle_dst[channel..], &rle_src);
             }
         }
 
-        // Leaves pre-multiplied
-        rle_dst.chunks_exact(TILE_SIZE * 4).fold(
-            // Offset of first element on the 32x32 tile within the final image.
-            pos2idx(y * width, x * TILE_SIZE, TILE_SIZE),
-            |offset, src| {
-                // Swaps BGRA -> RGBA
-                for (dst, src) in pixels[offset * 4..]
-                    .chunks_exact_mut(4)
-                    .zip(src.chunks_exact(4))
-                {
-                    dst[0] = src[2];
-                    dst[1] = src[1];
-                    dst[2] = src[0];
-                    dst[3] = src[3];
-                }
+        // Swaps BGRA -> RGBA, and leaves pre-multiplied. Every channel is fully overwritten by
+        // the next tile, so swapping in place is fine.
+        pixel_ops::swap_red_blue_in_place(&mut rle_dst);
 
-                // Skips `width` bytes to get the next row of the 32x32 tile.
-                offset + width
+        #[allow(clippy::cast_possible_truncation)]
+        f(
+            TileCoord {
+                x: x as u32,
+                y: y as u32,
             },
+            &rle_dst,
         );
     }
 
+    Ok(())
+}
+
+/// Same as [`decompress`], but for the grayscale pixels of a [`LayerKind::Mask`].
+fn decompress_mask<R>(reader: &mut BinReader<R>, dimensions: (usize, usize)) -> io::Result<Vec<u8>>
+where
+    R: Read,
+{
+    #[allow(clippy::cast_possible_truncation)]
+    decompress_mask_with(reader, dimensions, |value| {
+        (u32::from(value) * 255 / 0x4000).min(255) as u8
+    })
+}
+
+/// Same as [`decompress_mask`], but maps the raw value of every pixel ( where `0x4000` is fully
+/// opaque ) with `f`.
+fn decompress_mask_with<R, T, F>(
+    reader: &mut BinReader<R>,
+    (width, height): (usize, usize),
+    f: F,
+) -> io::Result<Vec<T>>
+where
+    R: Read,
+    T: Copy + Default,
+    F: Fn(u16) -> T,
+{
+    const TILE_SIZE: usize = TileCoord::SIZE as usize;
+
+    let tile_map_height = height / TILE_SIZE;
+    let tile_map_width = width / TILE_SIZE;
+
+    let mut tile_map = vec![0; tile_map_height * tile_map_width];
+    reader.read_exact(&mut tile_map)?;
+
+    let tile_map = tile_map;
+    let mut pixels = vec![T::default(); width * height];
+    let mut rle_dst = [0; PAGE_SIZE];
+    let mut rle_src = [0; PAGE_SIZE / 2];
+
+    for (y, x) in (0..tile_map_height)
+        .cartesian_product(0..tile_map_width)
+        .filter(|(y, x)| tile_map[y * tile_map_width + x] != 0)
+    {
+        // NOTE(rev-eng): Masks store 2 channels per tile, which are the low and high bytes of a
+        // 16 bits value, where `0x4000` is fully opaque.
+        for channel in 0..2 {
+            let size = reader.read_u16()?.into();
+            let Some(buf) = rle_src.get_mut(..size) else {
+                return Err(io::ErrorKind::InvalidData.into());
+            };
+            reader.read_exact(buf)?;
+
+            rle_decompress_stride::<4>(&mut rle_dst[channel..], &rle_src);
+        }
+
+        for (row, src) in rle_dst.chunks_exact(TILE_SIZE * 4).enumerate() {
+            let offset = (y * TILE_SIZE + row) * width + x * TILE_SIZE;
+            for (dst, src) in pixels[offset..][..TILE_SIZE]
+                .iter_mut()
+                .zip(src.chunks_exact(4))
+            {
+                *dst = f(u16::from_le_bytes([src[0], src[1]]));
+            }
+        }
+    }
+
     Ok(pixels)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header of a visible [`LayerKind::Regular`] layer.
+    const HEADER: [u8; Layer::HEADER_SIZE] = [
+        3, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 0, 0, 0, 32, 0, 0, 0, 0, 0, 0, 0, 100,
+        1, 0, 0, 0, b'm', b'r', b'o', b'n',
+    ];
+
+    #[test]
+    fn layer_kind_round_trips() {
+        for kind in [
+            LayerKind::RootLayer,
+            LayerKind::Regular,
+            LayerKind::_Unknown4,
+            LayerKind::Linework,
+            LayerKind::Mask,
+            LayerKind::_Unknown7,
+            LayerKind::Set,
+        ] {
+            assert_eq!(kind.to_string().parse::<LayerKind>(), Ok(kind));
+        }
+
+        assert_eq!(LayerKind::Linework.to_string(), "Linework");
+        assert_eq!(
+            "linework".parse::<LayerKind>(),
+            Err(ParseLayerKindError("linework".into()))
+        );
+        assert_eq!(
+            "Folder".parse::<LayerKind>().unwrap_err().to_string(),
+            r#""Folder" is not a valid layer kind"#
+        );
+    }
+
+    #[test]
+    fn interop_is_exact_works() {
+        assert!(BlendingMode::Multiply.interop_is_exact(InteropTarget::Css));
+        assert!(BlendingMode::Overlay.interop_is_exact(InteropTarget::Ora));
+        assert!(BlendingMode::Shade.interop_is_exact(InteropTarget::Psd));
+        assert!(!BlendingMode::Shade.interop_is_exact(InteropTarget::Ora));
+        assert!(!BlendingMode::Luminosity.interop_is_exact(InteropTarget::Css));
+        assert!(!BlendingMode::Binary.interop_is_exact(InteropTarget::Psd));
+    }
+
+    #[test]
+    fn to_css_works() {
+        assert_eq!(BlendingMode::Normal.to_css(), Some("normal"));
+        assert_eq!(BlendingMode::Multiply.to_css(), Some("multiply"));
+        assert_eq!(BlendingMode::Screen.to_css(), Some("screen"));
+        assert_eq!(BlendingMode::Overlay.to_css(), Some("overlay"));
+        assert_eq!(BlendingMode::Luminosity.to_css(), Some("plus-lighter"));
+        assert_eq!(BlendingMode::Shade.to_css(), None);
+        assert_eq!(BlendingMode::Binary.to_css(), None);
+    }
+
+    #[test]
+    fn decode_limits_take_the_pixel_size() -> io::Result<()> {
+        let bytes = [HEADER.as_slice(), &[0; 4]].concat();
+        let mut layer = Layer::from_reader(&mut bytes.as_slice(), false)?;
+        let mut limits = DecodeLimits {
+            max_total_bytes: 32 * 32,
+            ..Default::default()
+        };
+
+        // A 32x32 regular layer doesn't fit in 1024 bytes...
+        let err = limits.take(32, 32, layer.pixel_size()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+
+        // ...but a mask ( 1 byte per pixel ) does.
+        layer.kind = LayerKind::Mask;
+        limits.take(32, 32, layer.pixel_size())?;
+        assert_eq!(limits.max_total_bytes, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn compress_round_trips() -> io::Result<()> {
+        use crate::{internals::tests::resource, Sai};
+
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        for layer in sai.layers()? {
+            let Some(ref data) = layer.data else {
+                continue;
+            };
+
+            let compressed = layer.compress_data().unwrap();
+            let dimensions = (layer.bounds.width as usize, layer.bounds.height as usize);
+            let decompressed = decompress(&mut BinReader::new(compressed.as_slice()), dimensions)?;
+
+            assert_eq!(&decompressed, data);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn rle_compress_stride_round_trips() {
+        let mut src = [0; PAGE_SIZE];
+        for (idx, byte) in src.iter_mut().enumerate() {
+            // Mixes repeated runs (longer than 128) with literals.
+            *byte = if idx < 2048 { 7 } else { (idx % 5) as u8 };
+        }
+
+        let compressed = rle_compress_stride(&src);
+        let mut dst = [0; PAGE_SIZE];
+        rle_decompress_stride::<4>(&mut dst, &compressed);
+
+        assert!(src.iter().step_by(4).eq(dst.iter().step_by(4)));
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn rle_decompress_stride_works_without_stride() {
+        let mut src = [0; PAGE_SIZE];
+        for (idx, byte) in src.iter_mut().enumerate() {
+            *byte = if idx < 2048 { 7 } else { (idx % 5) as u8 };
+        }
+
+        let compressed = rle_compress_stride(&src);
+        let mut strided = [0; PAGE_SIZE];
+        rle_decompress_stride::<4>(&mut strided, &compressed);
+        let mut contiguous = [0; PAGE_SIZE / 4];
+        rle_decompress_stride::<1>(&mut contiguous, &compressed);
+
+        assert!(strided.iter().step_by(4).eq(contiguous.iter()));
+    }
+
+    #[test]
+    fn peff_skips_unknown_bytes() -> io::Result<()> {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(b"ffep");
+        bytes.extend_from_slice(&6u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 50, 3, 255, 0, 0]);
+        bytes.extend_from_slice(b"difp");
+        bytes.extend_from_slice(&4u32.to_le_bytes());
+        bytes.extend_from_slice(&7u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+
+        let layer = Layer::from_reader(&mut bytes.as_slice(), false)?;
+
+        assert_eq!(
+            layer.effect,
+            Some(Effect {
+                opacity: 50,
+                width: 3
+            })
+        );
+        assert_eq!(layer.parent_set, Some(7));
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::cast_sign_loss)]
+    fn crop_to_canvas_handles_negative_origins() -> io::Result<()> {
+        let bytes = [HEADER.as_slice(), &[0; 4]].concat();
+        let mut layer = Layer::from_reader(&mut bytes.as_slice(), false)?;
+        layer.bounds = LayerBounds {
+            x: -64,
+            y: 8,
+            width: 128,
+            height: 32,
+        };
+        // Opaque pixels, where the red channel holds the column of the pixel.
+        layer.data = Some(
+            (0..32 * 128)
+                .flat_map(|idx| [(idx % 128) as u8, 0, 0, 255])
+                .collect(),
+        );
+
+        let (rect, pixels) = layer.crop_to_canvas((100, 100)).unwrap();
+        assert_eq!(
+            rect,
+            LayerBounds {
+                x: 0,
+                y: 0,
+                width: 56,
+                height: 32,
+            }
+        );
+        assert_eq!(pixels.len(), 56 * 32 * 4);
+        assert_eq!(pixels[0], 72);
+
+        assert!(layer.crop_to_canvas((0, 0)).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn flip_works() -> io::Result<()> {
+        let bytes = [HEADER.as_slice(), &[0; 4]].concat();
+        let mut layer = Layer::from_reader(&mut bytes.as_slice(), false)?;
+        layer.bounds = LayerBounds {
+            x: 0,
+            y: 0,
+            width: 3,
+            height: 2,
+        };
+        // The red channel holds the index of the pixel.
+        layer.data = Some((0..6).flat_map(|idx| [idx, 0, 0, 255]).collect());
+        let red = |layer: &Layer| -> Vec<u8> {
+            layer
+                .data
+                .as_ref()
+                .unwrap()
+                .iter()
+                .step_by(4)
+                .copied()
+                .collect()
+        };
+
+        let mut flipped = layer.clone();
+        flipped.flip_horizontal();
+        assert_eq!(red(&flipped), [2, 1, 0, 5, 4, 3]);
+
+        let mut flipped = layer.clone();
+        flipped.flip_vertical();
+        assert_eq!(red(&flipped), [3, 4, 5, 0, 1, 2]);
+
+        let mut rotated = layer.clone();
+        rotated.rotate_180();
+        assert_eq!(red(&rotated), [5, 4, 3, 2, 1, 0]);
+
+        flipped.flip_horizontal();
+        assert_eq!(flipped, rotated);
+        assert_eq!(flipped.bounds, layer.bounds);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_kinds_work() -> io::Result<()> {
+        for (kind, expected) in [(4, LayerKind::_Unknown4), (7, LayerKind::_Unknown7)] {
+            let mut bytes = [HEADER.as_slice(), &[0; 4]].concat();
+            bytes[0] = kind;
+
+            let layer = Layer::from_reader(&mut bytes.as_slice(), true)?;
+            assert_eq!(layer.kind, expected);
+            assert_eq!(layer.data, None);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn mask_to_png_works() -> io::Result<()> {
+        use crate::{internals::tests::resource, Sai};
+
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        let mask = sai
+            .sublayers()?
+            .into_iter()
+            .find(|layer| layer.kind == LayerKind::Mask)
+            .unwrap();
+        let data = mask.data.as_ref().unwrap();
+        assert_eq!(
+            data.len(),
+            (mask.bounds.width * mask.bounds.height) as usize
+        );
+        assert!(data.iter().any(|&pixel| pixel != 0));
+
+        let path = std::env::temp_dir().join("saire-mask.png");
+        mask.to_png(Some(&path))?;
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path)?);
+        let mut reader = decoder.read_info()?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels)?;
+        std::fs::remove_file(path)?;
+
+        assert_eq!(info.color_type, png::ColorType::Grayscale);
+        assert_eq!(
+            (info.width, info.height),
+            (mask.bounds.width, mask.bounds.height)
+        );
+        assert_eq!(&pixels[..info.buffer_size()], data.as_slice());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "webp")]
+    #[allow(clippy::cast_precision_loss)]
+    fn to_webp_works() -> io::Result<()> {
+        use crate::{internals::tests::resource, Sai};
+
+        let layer = Sai::from(resource!("layerfullred.sai").as_slice())
+            .layers()?
+            .remove(0);
+        let (width, height, pixels) = layer.to_rgba().unwrap();
+        let path = std::env::temp_dir().join("saire-layer.webp");
+
+        // Lossy images subsample the colors, so they are only compared on average.
+        for (quality, tolerance) in [(None, 0.0), (Some(90.0), 2.0)] {
+            layer.to_webp(Some(&path), quality)?;
+            let bytes = std::fs::read(&path)?;
+            let decoded = webp::Decoder::new(&bytes).decode().unwrap();
+
+            assert_eq!((decoded.width(), decoded.height()), (width, height));
+            assert!(decoded.is_alpha());
+
+            let (mut difference, mut channels) = (0, 0);
+            for (expected, actual) in pixels.chunks_exact(4).zip(decoded.chunks_exact(4)) {
+                // The colors of fully transparent pixels are not kept ( even if lossless ).
+                let compared = if expected[3] == 0 { 3..4 } else { 0..4 };
+                for channel in compared {
+                    difference += u64::from(expected[channel].abs_diff(actual[channel]));
+                    channels += 1;
+                }
+            }
+            let mean = difference as f64 / f64::from(channels);
+            assert!(mean <= tolerance, "{mean} > {tolerance}");
+        }
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn to_binary_png_works() -> io::Result<()> {
+        use crate::internals::{image::decode_rgba, tests::resource};
+        use crate::Sai;
+
+        let sai = Sai::from(resource!("layerproperties.sai").as_slice());
+        let mask = sai
+            .sublayers()?
+            .into_iter()
+            .find(|layer| layer.kind == LayerKind::Mask)
+            .unwrap();
+
+        let path = std::env::temp_dir().join("saire-binary-mask.png");
+        mask.to_binary_png(Some(&path), 128)?;
+
+        let info = png::Decoder::new(std::fs::File::open(&path)?).read_info()?;
+        assert_eq!(info.info().color_type, png::ColorType::Grayscale);
+        assert_eq!(info.info().bit_depth, png::BitDepth::One);
+        let (width, height, pixels) = decode_rgba(std::fs::File::open(&path)?)?;
+        std::fs::remove_file(path)?;
+
+        assert_eq!((width, height), (mask.bounds.width, mask.bounds.height));
+        let expected: Vec<u8> = mask
+            .data
+            .unwrap()
+            .iter()
+            .flat_map(|&pixel| {
+                let value = if pixel >= 128 { 255 } else { 0 };
+                [value, value, value, 255]
+            })
+            .collect();
+        assert_eq!(pixels, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn layer_bounds_contains_works() {
+        let bounds = LayerBounds {
+            x: -8,
+            y: 16,
+            width: 32,
+            height: 64,
+        };
+
+        assert!(bounds.contains(0, 32));
+        assert!(bounds.contains(-8, 16));
+        assert!(bounds.contains(23, 79));
+        assert!(!bounds.contains(24, 32));
+        assert!(!bounds.contains(0, 80));
+        assert!(!bounds.contains(-9, 32));
+        assert!(!bounds.contains(0, 15));
+    }
+
+    #[test]
+    fn layer_bounds_overlaps_works() {
+        let bounds = LayerBounds {
+            x: 0,
+            y: 0,
+            width: 32,
+            height: 32,
+        };
+        let moved = |x, y| LayerBounds { x, y, ..bounds };
+
+        assert!(bounds.overlaps(&bounds));
+        assert!(bounds.overlaps(&moved(16, 16)));
+        assert!(bounds.overlaps(&moved(-31, -31)));
+        // Only touching the edges.
+        assert!(!bounds.overlaps(&moved(32, 0)));
+        assert!(!bounds.overlaps(&moved(0, -32)));
+        assert!(!bounds.overlaps(&moved(100, 100)));
+        assert!(!bounds.overlaps(&LayerBounds { width: 0, ..bounds }));
+    }
+}
@@ -64,6 +64,11 @@ pub struct LayerRef {
 }
 
 impl LayerTable {
+    /// Reads a `LayerTable` from the bytes of the `laytbl` ( or `subtbl` ) file.
+    ///
+    /// # Errors
+    ///
+    /// - If `reader` doesn't hold a valid layer table.
     pub fn from_reader<R>(reader: &mut R) -> io::Result<Self>
     where
         R: Read,
@@ -99,6 +104,11 @@ impl LayerTable {
         self.map.len()
     }
 
+    /// Returns `true` if this table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
     /// Gets a (index, [`LayerRef`]) pair of the specified layer `id`.
     pub fn get_full(&self, id: u32) -> Option<(usize, &LayerRef)> {
         self.map
@@ -108,7 +118,7 @@ impl LayerTable {
 
     /// Gets a [`LayerRef`] by index
     ///
-    /// Valid indices are *0 <= index < self.len()*.
+    /// Valid indices are *0 <= index < `self.len()`*.
     pub fn get_by_index(&self, index: usize) -> Option<&LayerRef> {
         self.map.get_index(index).map(|(_, layer)| layer)
     }
@@ -118,18 +128,25 @@ impl LayerTable {
         self.map.get_index_of(&id)
     }
 
-    /// Modifies a <code>[Vec]<[Layer]></code> to be ordered from `lowest` to
+    /// Gets the [`LayerKind`] of the specified layer `id`, if it exists in the table.
+    ///
+    /// Prefer this over indexing the table ( `laytbl[id].kind` ) whenever the `id` comes from
+    /// somewhere else than the table itself ( e.g: a [`Layer`] ), since both could disagree.
+    pub fn kind_of(&self, id: u32) -> Option<LayerKind> {
+        self.map.get(&id).map(|layer| layer.kind)
+    }
+
+    /// Modifies a <code>[[Layer]]</code> slice to be ordered from `lowest` to
     /// `highest`.
     ///
     /// If you ever wanna return to the original order, you can sort the layers
     /// by [`Layer::id`].
     ///
-    /// # Panics
-    ///
-    /// - If any of the of the [`Layer::id`]'s is not found in the [`LayerTable`].
-    pub fn sort_layers(&self, layers: &mut Vec<Layer>) {
+    /// Layers whose [`Layer::id`] is not found in the [`LayerTable`] (e.g: layers of an unknown
+    /// [`LayerKind`] that SAI didn't list) are moved to the top, keeping their relative order.
+    pub fn sort_layers(&self, layers: &mut [Layer]) {
         // TODO(Unavailable): would sort_by_key/sort_unstable_by_key work here?
-        layers.sort_by_cached_key(|e| self.map.get_full(&e.id).expect("id is found").0);
+        layers.sort_by_cached_key(|e| self.map.get_index_of(&e.id).unwrap_or(usize::MAX));
     }
 }
 
@@ -140,7 +157,8 @@ impl Index<u32> for LayerTable {
     ///
     /// # Panics
     ///
-    /// - If the id wasn't found.
+    /// - If the id wasn't found; see [`LayerTable::get_full`] or [`LayerTable::kind_of`] for
+    ///   non-panicking alternatives.
     #[inline]
     fn index(&self, id: u32) -> &Self::Output {
         &self.map[&id]
@@ -1,10 +1,12 @@
+pub mod atlas;
 pub mod canvas;
+pub mod composite;
 pub mod document;
 pub mod layer;
 pub mod thumbnail;
 
 pub mod prelude {
-    pub use super::{canvas::*, document::*, layer::*, thumbnail::*};
+    pub use super::{atlas::*, canvas::*, composite::*, document::*, layer::*, thumbnail::*};
 }
 
 // TODO(Unavailable): serde feature.
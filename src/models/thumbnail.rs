@@ -1,4 +1,6 @@
-use crate::internals::{binreader::BinReader, image::PngImage};
+use crate::internals::binreader::BinReader;
+#[cfg(feature = "png")]
+use crate::internals::image::{decode_rgba, PngImage};
 use std::io::{self, Read};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -12,6 +14,12 @@ pub struct Thumbnail {
 }
 
 impl Thumbnail {
+    /// Reads a thumbnail stored as `BM32` (BGRA pixels), or as an embedded png image (only with
+    /// the `png` feature).
+    ///
+    /// # Errors
+    ///
+    /// - If `reader` doesn't hold a valid thumbnail ( or a png one without the `png` feature ).
     pub fn from_reader<R>(reader: &mut R) -> io::Result<Self>
     where
         R: Read,
@@ -21,12 +29,24 @@ impl Thumbnail {
         let width = reader.read_u32()?;
         let height = reader.read_u32()?;
 
-        let magic = reader.read_array()?;
-        if &magic != b"BM32" {
-            return Err(io::ErrorKind::InvalidData.into());
+        let magic: [u8; 4] = reader.read_array()?;
+        match &magic {
+            b"BM32" => {}
+            #[cfg(feature = "png")]
+            b"\x89PNG" => {
+                let (width, height, pixels) = decode_rgba(magic.as_slice().chain(reader))?;
+                return Ok(Self {
+                    width,
+                    height,
+                    pixels,
+                });
+            }
+            _ => return Err(io::ErrorKind::InvalidData.into()),
         }
 
-        let pixels_len = (width * height * 4) as usize;
+        let pixels_len = (width as usize)
+            .checked_mul(height as usize * 4)
+            .ok_or(io::ErrorKind::InvalidData)?;
         let mut pixels = vec![0; pixels_len];
         reader.read_exact(pixels.as_mut_slice())?;
 
@@ -41,6 +61,12 @@ impl Thumbnail {
         })
     }
 
+    /// Gets the `width`, `height` and pixels ( RGBA, row by row, from the top-left corner ) of
+    /// this `Thumbnail`, without needing any image encoding feature.
+    pub fn to_rgba(&self) -> (u32, u32, Vec<u8>) {
+        (self.width, self.height, self.pixels.clone())
+    }
+
     /// Gets a png image from the underlying `Thumbnail` pixels.
     ///
     /// # Errors
@@ -58,4 +84,53 @@ impl Thumbnail {
         };
         png.save(&self.pixels, path)
     }
+
+    /// Gets an [`image::RgbaImage`] from the underlying `Thumbnail` pixels.
+    ///
+    /// # Panics
+    ///
+    /// - If `pixels` were modified to not hold `width * height` RGBA pixels.
+    #[cfg(feature = "image")]
+    pub fn to_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_raw(self.width, self.height, self.pixels.clone())
+            .expect("pixels are width * height * 4 long")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_rgba_works() -> io::Result<()> {
+        let mut bytes = [2u32, 1].map(u32::to_le_bytes).concat();
+        bytes.extend_from_slice(b"BM32");
+        bytes.extend_from_slice(&[0, 0, 255, 255, 0, 255, 0, 128]);
+
+        let thumbnail = Thumbnail::from_reader(&mut bytes.as_slice())?;
+        assert_eq!(
+            thumbnail.to_rgba(),
+            (2, 1, vec![255, 0, 0, 255, 0, 255, 0, 128])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn png_thumbnail_works() -> io::Result<()> {
+        let pixels = [255, 0, 0, 255, 0, 255, 0, 128];
+
+        let mut bytes = [2u32, 1].map(u32::to_le_bytes).concat();
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.write_header()?.write_image_data(&pixels)?;
+
+        let thumbnail = Thumbnail::from_reader(&mut bytes.as_slice())?;
+
+        assert_eq!((thumbnail.width, thumbnail.height), (2, 1));
+        assert_eq!(thumbnail.pixels, pixels);
+
+        Ok(())
+    }
 }
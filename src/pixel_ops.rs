@@ -1,32 +1,480 @@
+use crate::{internals::compositor, models::layer::BlendingMode};
+
 /// Converts from RGBA `pre-multiplied alpha` to RGBA `straight` color format.
+///
+/// # Panics
+///
+/// - If `pixels.len()` is not a multiple of 4.
 pub fn premultiplied_to_straight(pixels: &[u8]) -> Vec<u8> {
-    assert!(pixels.len() % 4 == 0);
+    assert!(pixels.len().is_multiple_of(4));
 
     pixels
         .chunks_exact(4)
-        .flat_map(|chunk| {
-            let scale = chunk[3] as f32 / 255.0;
-
-            let quad_pixel = chunk.try_into().expect("chunk_exact(4)");
-            let mut quad_pixel = i32::from_le_bytes(quad_pixel);
-            for c in 0..3 {
-                let mut cur_channel = quad_pixel >> (c * 8);
-                cur_channel &= 255;
-                let mut channel_float = cur_channel as f32;
-
-                channel_float /= 255.0;
-                channel_float /= scale;
-                channel_float *= 255.0;
-
-                cur_channel = channel_float as i32;
-                cur_channel &= 255;
-                cur_channel <<= c * 8;
-
-                quad_pixel &= !(0xFF << (c * 8));
-                quad_pixel |= cur_channel;
-            }
+        .flat_map(|chunk| to_straight(chunk.try_into().expect("chunk_exact(4)")))
+        .collect()
+}
+
+/// Same as [`premultiplied_to_straight`], but overwrites `pixels` instead of allocating a new
+/// buffer.
+///
+/// # Panics
+///
+/// - If `pixels.len()` is not a multiple of 4.
+pub fn premultiplied_to_straight_in_place(pixels: &mut [u8]) {
+    assert!(pixels.len().is_multiple_of(4));
+
+    for chunk in pixels.chunks_exact_mut(4) {
+        let pixel = to_straight(chunk.try_into().expect("chunk_exact_mut(4)"));
+        chunk.copy_from_slice(&pixel);
+    }
+}
+
+/// Converts from RGBA `straight` to RGBA `pre-multiplied alpha` color format, overwriting
+/// `pixels`.
+///
+/// # Panics
+///
+/// - If `pixels.len()` is not a multiple of 4.
+#[allow(clippy::cast_possible_truncation)]
+pub fn straight_to_premultiplied_in_place(pixels: &mut [u8]) {
+    assert!(pixels.len().is_multiple_of(4));
+
+    for chunk in pixels.chunks_exact_mut(4) {
+        let alpha = u32::from(chunk[3]);
+        for channel in &mut chunk[..3] {
+            *channel = ((u32::from(*channel) * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+/// The order of the channels of each pixel.
+///
+/// SAI stores pixels as BGRA, but [`Layer::data`] is always RGBA; see
+/// [`Layer::data_with_order`].
+///
+/// [`Layer::data`]: crate::models::layer::Layer::data
+/// [`Layer::data_with_order`]: crate::models::layer::Layer::data_with_order
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChannelOrder {
+    #[default]
+    Rgba,
+    Bgra,
+}
+
+/// How the color channels of each pixel relate to its alpha channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// The color channels are already multiplied by the alpha; this is how SAI stores them.
+    Premultiplied,
+    /// The color channels are independent of the alpha; see [`premultiplied_to_straight`].
+    Straight,
+}
+
+/// Swaps the red and blue channels of every pixel, which converts RGBA `pixels` to BGRA ( and
+/// the other way around ).
+///
+/// # Panics
+///
+/// - If `pixels.len()` is not a multiple of 4.
+pub fn swap_red_blue_in_place(pixels: &mut [u8]) {
+    assert!(pixels.len().is_multiple_of(4));
+
+    pixels
+        .chunks_exact_mut(4)
+        .for_each(|pixel| pixel.swap(0, 2));
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn to_straight(quad_pixel: [u8; 4]) -> [u8; 4] {
+    let scale = quad_pixel[3] as f32 / 255.0;
+
+    let mut quad_pixel = i32::from_le_bytes(quad_pixel);
+    for c in 0..3 {
+        let mut cur_channel = quad_pixel >> (c * 8);
+        cur_channel &= 255;
+        let mut channel_float = cur_channel as f32;
+
+        channel_float /= 255.0;
+        channel_float /= scale;
+        channel_float *= 255.0;
 
-            i32::to_le_bytes(quad_pixel)
-        })
+        cur_channel = channel_float as i32;
+        cur_channel &= 255;
+        cur_channel <<= c * 8;
+
+        quad_pixel &= !(0xFF << (c * 8));
+        quad_pixel |= cur_channel;
+    }
+
+    i32::to_le_bytes(quad_pixel)
+}
+
+/// Copies the `(x, y, width, height)` rectangle out of a RGBA image that is `stride` pixels wide.
+///
+/// # Panics
+///
+/// - If the rectangle is not inside of the image.
+pub fn crop_rgba(
+    pixels: &[u8],
+    stride: u32,
+    (x, y, width, height): (u32, u32, u32, u32),
+) -> Vec<u8> {
+    let (stride, x, width) = (stride as usize * 4, x as usize * 4, width as usize * 4);
+
+    pixels
+        .chunks_exact(stride)
+        .skip(y as usize)
+        .take(height as usize)
+        .flat_map(|row| &row[x..x + width])
+        .copied()
         .collect()
 }
+
+/// Downscales a RGBA image from `(width, height)` to `(new_width, new_height)`, averaging all the
+/// pixels that fall on the same destination pixel ( box filter ).
+///
+/// If the image holds `straight` colors, the transparent pixels will bleed their color into the
+/// result; prefer using `pre-multiplied alpha` images instead.
+///
+/// # Panics
+///
+/// - If `pixels.len()` doesn't match `width * height * 4`.
+///
+/// - If the new size is bigger than the current one.
+#[allow(clippy::cast_possible_truncation)]
+pub fn downscale_rgba(
+    pixels: &[u8],
+    (width, height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+) -> Vec<u8> {
+    assert_eq!(pixels.len(), width as usize * height as usize * 4);
+    assert!(new_width <= width && new_height <= height);
+
+    let (width, height) = (width as usize, height as usize);
+    let (new_width, new_height) = (new_width as usize, new_height as usize);
+    let span = |dst: usize, src_len: usize, dst_len: usize| {
+        dst * src_len / dst_len..((dst + 1) * src_len / dst_len).max(dst * src_len / dst_len + 1)
+    };
+
+    let mut scaled = Vec::with_capacity(new_width * new_height * 4);
+    for dst_y in 0..new_height {
+        let rows = span(dst_y, height, new_height);
+        for dst_x in 0..new_width {
+            let columns = span(dst_x, width, new_width);
+
+            let mut sum = [0u64; 4];
+            for y in rows.clone() {
+                for x in columns.clone() {
+                    let pixel = &pixels[(y * width + x) * 4..][..4];
+                    sum.iter_mut().zip(pixel).for_each(|(s, &c)| *s += c as u64);
+                }
+            }
+
+            let count = (rows.len() * columns.len()) as u64;
+            scaled.extend(sum.map(|s| (s / count) as u8));
+        }
+    }
+
+    scaled
+}
+
+// Blending functions.
+//
+// All of them work on a single channel of a RGBA `pre-multiplied alpha` pixel, where `bg` is the
+// color already on the image, and `fg` the color of the layer being blended on top of it. The
+// alpha channel of the result is always `normal(bg_a, fg_a, fg_a)`, except for `binary`.
+
+#[inline]
+fn unorm(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+#[inline]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn from_unorm(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Blends `fg` on top of `bg` with the `over` operator: `fg + bg * (1 - fg_a)`.
+#[inline]
+pub fn normal(bg: u8, fg: u8, fg_a: u8) -> u8 {
+    let (bg, fg, fg_a) = (bg as u32, fg as u32, fg_a as u32);
+    (fg + (bg * (255 - fg_a) + 127) / 255).min(255) as u8
+}
+
+/// Multiplies the colors of `fg` and `bg`; the result is always darker.
+#[inline]
+pub fn multiply(bg: u8, fg: u8, bg_a: u8, fg_a: u8) -> u8 {
+    let (bg, fg, bg_a, fg_a) = (unorm(bg), unorm(fg), unorm(bg_a), unorm(fg_a));
+    from_unorm(fg * bg + fg * (1.0 - bg_a) + bg * (1.0 - fg_a))
+}
+
+/// Multiplies the inverse of the colors of `fg` and `bg`; the result is always lighter.
+#[inline]
+pub fn screen(bg: u8, fg: u8, _bg_a: u8, _fg_a: u8) -> u8 {
+    let (bg, fg) = (unorm(bg), unorm(fg));
+    from_unorm(fg + bg - fg * bg)
+}
+
+/// [`multiply`] on the dark colors of `bg`, and [`screen`] on the light ones.
+#[inline]
+pub fn overlay(bg: u8, fg: u8, bg_a: u8, fg_a: u8) -> u8 {
+    let (bg, fg, bg_a, fg_a) = (unorm(bg), unorm(fg), unorm(bg_a), unorm(fg_a));
+    let blended = if 2.0 * bg <= bg_a {
+        2.0 * fg * bg
+    } else {
+        fg_a * bg_a - 2.0 * (bg_a - bg) * (fg_a - fg)
+    };
+    from_unorm(blended + fg * (1.0 - bg_a) + bg * (1.0 - fg_a))
+}
+
+/// Adds the colors of `fg` and `bg` ( also known as `linear dodge` ).
+///
+/// SAI calls this blending mode `Luminosity`, but it has nothing to do with the `luminosity`
+/// mode of other programs.
+#[inline]
+pub fn luminosity(bg: u8, fg: u8, bg_a: u8, fg_a: u8) -> u8 {
+    let (bg, fg, bg_a, fg_a) = (unorm(bg), unorm(fg), unorm(bg_a), unorm(fg_a));
+    let blended = (fg * bg_a + bg * fg_a).min(fg_a * bg_a);
+    from_unorm(blended + fg * (1.0 - bg_a) + bg * (1.0 - fg_a))
+}
+
+/// Adds the colors of `fg` and `bg`, and subtracts white ( also known as `linear burn` ); the
+/// result is always darker.
+///
+/// SAI calls this blending mode `Shade`. The result is clamped to black, even where `bg` is
+/// fully transparent.
+#[inline]
+pub fn shade(bg: u8, fg: u8, bg_a: u8, fg_a: u8) -> u8 {
+    let (bg, fg, bg_a, fg_a) = (unorm(bg), unorm(fg), unorm(bg_a), unorm(fg_a));
+    let blended = (fg * bg_a + bg * fg_a - fg_a * bg_a).max(0.0);
+    from_unorm(blended + fg * (1.0 - bg_a) + bg * (1.0 - fg_a))
+}
+
+/// Adds twice the color of `fg` to `bg`, and subtracts white ( also known as `linear light` );
+/// the result is lighter where `fg` is light, and darker where `fg` is dark.
+///
+/// SAI calls this blending mode `Lumi & Shade`, since it behaves like [`luminosity`] and
+/// [`shade`] combined.
+#[inline]
+pub fn lumi_shade(bg: u8, fg: u8, bg_a: u8, fg_a: u8) -> u8 {
+    let (bg, fg, bg_a, fg_a) = (unorm(bg), unorm(fg), unorm(bg_a), unorm(fg_a));
+    let blended = (2.0 * fg * bg_a + bg * fg_a - fg_a * bg_a).clamp(0.0, fg_a * bg_a);
+    from_unorm(blended + fg * (1.0 - bg_a) + bg * (1.0 - fg_a))
+}
+
+/// Draws `fg` fully opaque where `fg_a` is at least `128`, and leaves `bg` as is everywhere
+/// else.
+///
+/// SAI calls this blending mode `Binary`. Partial alpha is ignored, since `fg_a` is only used as
+/// a 1-bit mask; the alpha channel of the result is `binary(bg_a, fg_a, fg_a)`, which is either
+/// `255` or `bg_a`.
+///
+/// When flattening layers, the threshold is applied to the alpha of the layer pixels, and the
+/// layer `opacity` is applied afterwards; a `Binary` layer at 40% opacity is still drawn, at 40%.
+#[inline]
+pub fn binary(bg: u8, fg: u8, fg_a: u8) -> u8 {
+    if fg_a < 128 {
+        return bg;
+    }
+
+    let (fg, fg_a) = (fg as u32, fg_a as u32);
+    ((fg * 255 + fg_a / 2) / fg_a).min(255) as u8
+}
+
+/// Blends the `fg` image on top of the `bg` one with `mode`, where both are RGBA `straight`
+/// images of the same size, returning a `straight` image.
+///
+/// The images are converted to `pre-multiplied alpha` before blending them, so this gives the
+/// same result as doing so manually.
+///
+/// # Panics
+///
+/// - If `bg.len()` and `fg.len()` are different, or if they are not a multiple of 4.
+pub fn composite_straight(bg: &[u8], fg: &[u8], mode: BlendingMode) -> Vec<u8> {
+    assert_eq!(bg.len(), fg.len());
+
+    let (mut image, mut fg) = (bg.to_vec(), fg.to_vec());
+    straight_to_premultiplied_in_place(&mut image);
+    straight_to_premultiplied_in_place(&mut fg);
+
+    let blend = (compositor::blend_fn(mode), compositor::alpha_fn(mode));
+    for (dst, src) in image.chunks_exact_mut(4).zip(fg.chunks_exact(4)) {
+        compositor::blend_pixel(dst, src.try_into().expect("chunk_exact(4)"), blend);
+    }
+
+    premultiplied_to_straight_in_place(&mut image);
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composite_straight_works() {
+        let (bg, fg) = ([200, 100, 50, 255], [20, 240, 128, 128]);
+
+        let mut premultiplied = [bg, fg];
+        for pixel in &mut premultiplied {
+            straight_to_premultiplied_in_place(pixel);
+        }
+        let [mut expected, fg_premultiplied] = premultiplied;
+        for c in 0..3 {
+            expected[c] = multiply(
+                expected[c],
+                fg_premultiplied[c],
+                expected[3],
+                fg_premultiplied[3],
+            );
+        }
+        expected[3] = normal(expected[3], fg_premultiplied[3], fg_premultiplied[3]);
+        premultiplied_to_straight_in_place(&mut expected);
+
+        assert_eq!(
+            composite_straight(&bg, &fg, BlendingMode::Multiply),
+            expected
+        );
+        assert_eq!(composite_straight(&bg, &[0; 4], BlendingMode::Normal), bg);
+    }
+
+    type Blend = fn(u8, u8, u8, u8) -> u8;
+    /// `(bg, fg, bg_a, fg_a) => expected`, for each one of the blends.
+    type Tile<const N: usize> = [((u8, u8, u8, u8), [u8; N])];
+
+    const NORMAL: Blend = |bg, fg, _, fg_a| normal(bg, fg, fg_a);
+
+    fn assert_tile<const N: usize>(blends: [(&str, Blend); N], tile: &Tile<N>, tolerance: u8) {
+        for &((bg, fg, bg_a, fg_a), expected) in tile {
+            for ((name, blend), expected) in blends.iter().zip(expected) {
+                let actual = blend(bg, fg, bg_a, fg_a);
+                assert!(
+                    actual.abs_diff(expected) <= tolerance,
+                    "{name}: {bg} {fg} {bg_a} {fg_a} => {actual}, expected {expected}"
+                );
+            }
+        }
+    }
+
+    const BLENDS: [(&str, Blend); 5] = [
+        ("normal", NORMAL),
+        ("multiply", multiply),
+        ("screen", screen),
+        ("overlay", overlay),
+        ("luminosity", luminosity),
+    ];
+
+    #[test]
+    fn blending_matches_sai() {
+        // Taken from `layerproperties.sai` ( fully opaque pixels that only overlap with the
+        // background ), where the expected values come from `layerproperties.png`.
+        //
+        // SAI rounds some of the divisions differently, so the values might be off by one.
+        //
+        // (bg, fg, bg_a, fg_a) => [normal, multiply, screen, overlay, luminosity]
+        let tile = [
+            ((6, 153, 255, 255), [153, 3, 156, 7, 159]),
+            ((108, 55, 255, 255), [55, 23, 140, 46, 164]),
+            ((129, 69, 255, 255), [69, 35, 164, 72, 199]),
+        ];
+
+        assert_tile(BLENDS, &tile, 1);
+    }
+
+    #[test]
+    fn blending_works() {
+        // (bg, fg, bg_a, fg_a) => [normal, multiply, screen, overlay, luminosity]
+        let tile = [
+            ((6, 153, 255, 255), [153, 4, 155, 7, 159]),
+            ((108, 55, 255, 255), [55, 23, 140, 47, 163]),
+            ((129, 69, 255, 255), [69, 35, 163, 71, 198]),
+            ((255, 255, 255, 255), [255, 255, 255, 255, 255]),
+            ((0, 0, 255, 255), [0, 0, 0, 0, 0]),
+            ((255, 0, 255, 255), [0, 0, 255, 255, 255]),
+            ((128, 128, 255, 255), [128, 64, 192, 128, 255]),
+            // Transparent background: only `fg` is left.
+            ((0, 100, 0, 255), [100, 100, 100, 100, 100]),
+            // Transparent foreground: only `bg` is left.
+            ((90, 0, 255, 0), [90, 90, 90, 90, 90]),
+            // Half transparent `fg` ( scaled by the layer opacity ).
+            ((200, 64, 255, 128), [164, 150, 214, 200, 228]),
+        ];
+
+        assert_tile(BLENDS, &tile, 0);
+    }
+
+    #[test]
+    fn shade_works() {
+        // (bg, fg, bg_a, fg_a) => expected
+        let tile = [
+            ((255, 255, 255, 255), 255),
+            ((128, 128, 255, 255), 1),
+            ((64, 64, 255, 255), 0),
+            ((200, 0, 255, 255), 0),
+            // Transparent background: only `fg` is left.
+            ((0, 100, 0, 255), 100),
+            ((0, 0, 0, 255), 0),
+            // Transparent foreground: only `bg` is left.
+            ((90, 0, 255, 0), 90),
+        ];
+
+        for ((bg, fg, bg_a, fg_a), expected) in tile {
+            assert_eq!(
+                shade(bg, fg, bg_a, fg_a),
+                expected,
+                "{bg} {fg} {bg_a} {fg_a}"
+            );
+        }
+    }
+
+    #[test]
+    fn binary_works() {
+        let bg = [0, 255, 0, 255].repeat(256);
+        // Magenta, going from fully transparent to fully opaque.
+        let fg: Vec<u8> = (0..=255).flat_map(|alpha| [255, 0, 255, alpha]).collect();
+
+        let image = composite_straight(&bg, &fg, BlendingMode::Binary);
+        for (alpha, pixel) in image.chunks_exact(4).enumerate() {
+            let expected = if alpha < 128 {
+                &bg[..4]
+            } else {
+                &[255, 0, 255, 255]
+            };
+            assert_eq!(pixel, expected, "{alpha}");
+        }
+
+        // Transparent background.
+        assert_eq!(binary(0, 64, 128), 128);
+        assert_eq!(binary(0, 128, 127), 0);
+        assert_eq!(binary(0, 128, 128), 255);
+    }
+
+    #[test]
+    fn lumi_shade_works() {
+        // (bg, fg, bg_a, fg_a) => expected
+        let tile = [
+            // Taken from `layerproperties.sai`.
+            ((6, 153, 255, 255), 57),
+            ((108, 55, 255, 255), 0),
+            ((129, 69, 255, 255), 12),
+            // A middle gray `fg` leaves `bg` (almost) as is.
+            ((90, 128, 255, 255), 91),
+            ((200, 255, 255, 255), 255),
+            // Transparent background: only `fg` is left.
+            ((0, 100, 0, 255), 100),
+            // Transparent foreground: only `bg` is left.
+            ((90, 0, 255, 0), 90),
+            // Half transparent `fg` ( scaled by the layer opacity ).
+            ((200, 64, 255, 128), 200),
+            ((200, 0, 255, 128), 100),
+        ];
+
+        for ((bg, fg, bg_a, fg_a), expected) in tile {
+            assert_eq!(
+                lumi_shade(bg, fg, bg_a, fg_a),
+                expected,
+                "{bg} {fg} {bg_a} {fg_a}"
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Consistency checks between the different files of a `.sai` document.
+//!
+//! A failed check doesn't stop the file from being read, but it usually means that something was
+//! parsed wrong, or that the file comes from an unsupported variant.
+
+use crate::models::prelude::*;
+use std::fmt::{self, Display, Formatter};
+
+/// Problems found by [`Sai::validate`].
+///
+/// [`Sai::validate`]: crate::Sai::validate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationWarning {
+    /// [`Canvas::selected_layer`] is not part of the [`LayerTable`].
+    MissingSelectedLayer(u32),
+    /// There is no [`LayerTable`], so the layers are stacked on the order that they were found
+    /// on the file system.
+    MissingLayerTable,
+    /// A [`LayerKind::Mask`] is listed on the `subtbl`, but it is not on the `sublayers`.
+    MissingMask(u32),
+    /// A [`LayerKind::Mask`] is on the `sublayers`, but it is not listed on the `subtbl`.
+    UnlistedMask(u32),
+}
+
+impl Display for ValidationWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingSelectedLayer(id) => {
+                write!(f, "selected layer {id} is not on the layer table")
+            }
+            Self::MissingLayerTable => write!(f, "there is no layer table"),
+            Self::MissingMask(id) => write!(f, "mask {id} is on the sublayer table, but not found"),
+            Self::UnlistedMask(id) => write!(f, "mask {id} is not on the sublayer table"),
+        }
+    }
+}
+
+/// Implausible [`Canvas`] metadata found by [`Sai::suggest_canvas_fixes`].
+///
+/// [`Sai::suggest_canvas_fixes`]: crate::Sai::suggest_canvas_fixes
+#[derive(Clone, Debug, PartialEq)]
+pub enum CanvasWarning {
+    /// [`Canvas::width`] or [`Canvas::height`] are `0`.
+    ZeroDimensions { width: u32, height: u32 },
+    /// [`Canvas::dots_per_inch`] is not bigger than `0`.
+    InvalidResolution(f32),
+    /// Same as [`ValidationWarning::MissingSelectedLayer`].
+    MissingSelectedLayer(u32),
+    /// The [`Thumbnail`] doesn't have the same aspect ratio as the canvas.
+    ThumbnailAspectRatio {
+        canvas: (u32, u32),
+        thumbnail: (u32, u32),
+    },
+}
+
+impl Display for CanvasWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroDimensions { width, height } => {
+                write!(f, "canvas has zero dimensions ({width}x{height})")
+            }
+            Self::InvalidResolution(dpi) => write!(f, "canvas resolution is {dpi} dpi"),
+            Self::MissingSelectedLayer(id) => {
+                write!(f, "selected layer {id} is not on the layer table")
+            }
+            Self::ThumbnailAspectRatio {
+                canvas: (width, height),
+                thumbnail: (thumbnail_width, thumbnail_height),
+            } => write!(
+                f,
+                "thumbnail ({thumbnail_width}x{thumbnail_height}) doesn't have the aspect ratio \
+                 of the canvas ({width}x{height})"
+            ),
+        }
+    }
+}
+
+/// Checks the metadata of `canvas`; the `laytbl` and `thumbnail` checks are skipped if they are
+/// not available.
+pub(crate) fn canvas_fixes(
+    canvas: &Canvas,
+    laytbl: Option<&LayerTable>,
+    thumbnail: Option<&Thumbnail>,
+) -> Vec<CanvasWarning> {
+    let (width, height) = (canvas.width, canvas.height);
+    let mut warnings = Vec::new();
+
+    if width == 0 || height == 0 {
+        warnings.push(CanvasWarning::ZeroDimensions { width, height });
+    }
+
+    if let Some(dpi) = canvas.dots_per_inch().filter(|&dpi| dpi <= 0.0) {
+        warnings.push(CanvasWarning::InvalidResolution(dpi));
+    }
+
+    if let Some(ValidationWarning::MissingSelectedLayer(id)) =
+        laytbl.and_then(|laytbl| selected_layer(canvas, laytbl))
+    {
+        warnings.push(CanvasWarning::MissingSelectedLayer(id));
+    }
+
+    if let Some(thumbnail) = thumbnail {
+        let (w, h) = (thumbnail.width as u64, thumbnail.height as u64);
+        // Allows the thumbnail to be off by a pixel, because of rounding.
+        if (w * height as u64).abs_diff(h * width as u64) > width.max(height) as u64 {
+            warnings.push(CanvasWarning::ThumbnailAspectRatio {
+                canvas: (width, height),
+                thumbnail: (thumbnail.width, thumbnail.height),
+            });
+        }
+    }
+
+    warnings
+}
+
+pub(crate) fn selected_layer(canvas: &Canvas, laytbl: &LayerTable) -> Option<ValidationWarning> {
+    canvas
+        .selected_layer
+        .filter(|&id| laytbl.get_index_of(id).is_none())
+        .map(ValidationWarning::MissingSelectedLayer)
+}
+
+/// Checks that the masks listed on `subtbl` ( if any ) are the same ones found on `sublayers`.
+pub(crate) fn masks(subtbl: Option<&LayerTable>, sublayers: &[Layer]) -> Vec<ValidationWarning> {
+    let listed: Vec<_> = subtbl
+        .into_iter()
+        .flat_map(|subtbl| subtbl.clone().into_iter())
+        .filter(|(_, layer)| layer.kind == LayerKind::Mask)
+        .map(|(_, layer)| layer.id)
+        .collect();
+    let found: Vec<_> = sublayers
+        .iter()
+        .filter(|layer| layer.kind == LayerKind::Mask)
+        .map(|layer| layer.id)
+        .collect();
+
+    let missing = listed
+        .iter()
+        .filter(|id| !found.contains(id))
+        .map(|&id| ValidationWarning::MissingMask(id));
+    let unlisted = found
+        .iter()
+        .filter(|id| !listed.contains(id))
+        .map(|&id| ValidationWarning::UnlistedMask(id));
+
+    missing.chain(unlisted).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A [`LayerTable`] with the layers `2` and `3`.
+    const LAYTBL: [u8; 20] = [2, 0, 0, 0, 2, 0, 0, 0, 3, 0, 1, 0, 3, 0, 0, 0, 3, 0, 1, 0];
+
+    fn thumbnail(width: u32, height: u32) -> Thumbnail {
+        Thumbnail {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    fn canvas(selected_layer: Option<u32>) -> Canvas {
+        Canvas {
+            alignment: 16,
+            width: 512,
+            height: 512,
+            resolution: Some(72 << 16),
+            size_unit: None,
+            resolution_unit: None,
+            selection_source: None,
+            selected_layer,
+        }
+    }
+
+    #[test]
+    fn selected_layer_works() {
+        let laytbl = LayerTable::from_reader(&mut LAYTBL.as_slice()).unwrap();
+
+        assert_eq!(selected_layer(&canvas(Some(3)), &laytbl), None);
+        assert_eq!(selected_layer(&canvas(None), &laytbl), None);
+        assert_eq!(
+            selected_layer(&canvas(Some(9)), &laytbl),
+            Some(ValidationWarning::MissingSelectedLayer(9))
+        );
+    }
+
+    #[test]
+    fn canvas_fixes_works() {
+        let laytbl = LayerTable::from_reader(&mut LAYTBL.as_slice()).unwrap();
+
+        let canvas = canvas(Some(2));
+        assert_eq!(
+            canvas_fixes(&canvas, Some(&laytbl), Some(&thumbnail(256, 256))),
+            []
+        );
+
+        let canvas = Canvas {
+            resolution: Some(0),
+            width: 0,
+            ..canvas
+        };
+        assert_eq!(
+            canvas_fixes(&canvas, None, None),
+            [
+                CanvasWarning::ZeroDimensions {
+                    width: 0,
+                    height: 512
+                },
+                CanvasWarning::InvalidResolution(0.0)
+            ]
+        );
+
+        let canvas = Canvas {
+            width: 1024,
+            ..canvas
+        };
+        assert_eq!(
+            canvas_fixes(&canvas, Some(&laytbl), Some(&thumbnail(128, 128)))[1..],
+            [CanvasWarning::ThumbnailAspectRatio {
+                canvas: (1024, 512),
+                thumbnail: (128, 128)
+            }]
+        );
+        assert_eq!(
+            canvas_fixes(&canvas, Some(&laytbl), Some(&thumbnail(256, 127)))[1..],
+            []
+        );
+    }
+}
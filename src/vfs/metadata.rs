@@ -0,0 +1,119 @@
+// TODO: Remove when making "pub"
+#![allow(dead_code)]
+
+use crate::{
+    cipher::{FatEntry, FatKind},
+    vfs::{EntryLocation, FileSystemReader, ROOT_INDEX},
+};
+use std::{
+    io,
+    path::{Component, Path},
+    time::SystemTime,
+};
+
+/// Information about an entry of the file system; see [`FileSystemReader::metadata`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Metadata {
+    /// The amount of bytes of the entry contents.
+    pub size: u32,
+    pub kind: FatKind,
+    pub modified: SystemTime,
+}
+
+impl From<&FatEntry> for Metadata {
+    fn from(entry: &FatEntry) -> Self {
+        Self {
+            size: entry.size(),
+            kind: entry.kind().expect("entry is not empty"),
+            modified: entry.modified_time(),
+        }
+    }
+}
+
+impl FileSystemReader {
+    /// Gets the [`Metadata`] of the entry found at `path` (i.e: `/layers/00000002`), without
+    /// reading its contents.
+    ///
+    /// # Errors
+    ///
+    /// - If `path` doesn't point to any entry.
+    pub(crate) fn metadata(&self, path: impl AsRef<Path>) -> io::Result<Metadata> {
+        let path = path.as_ref();
+        let not_found = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} entry was not found", path.display()),
+            )
+        };
+
+        let mut entry: Option<FatEntry> = None;
+        for component in path.components() {
+            let name = match component {
+                Component::RootDir => continue,
+                Component::Normal(name) => name.to_str().ok_or_else(not_found)?,
+                _ => return Err(not_found()),
+            };
+
+            let folder = match entry {
+                None => ROOT_INDEX,
+                Some(ref entry) if entry.kind() == Some(FatKind::Folder) => {
+                    entry.next_block() as usize
+                }
+                Some(_) => return Err(not_found()),
+            };
+            entry = Some(self.find_entry(folder, name).ok_or_else(not_found)?.1);
+        }
+
+        entry.as_ref().map(Metadata::from).ok_or_else(not_found)
+    }
+
+    /// Finds the entry called `name` directly inside of the folder that starts at `index`, and
+    /// where it is located.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(super) fn find_entry(&self, index: usize, name: &str) -> Option<(EntryLocation, FatEntry)> {
+        let mut next_index = Some(index as u32);
+        while let Some(index) = next_index {
+            let (data, next_block) = self.read_data(index as usize);
+            next_index = next_block;
+
+            if let Some(slot) = data
+                .iter()
+                .take_while(|entry| entry.flags() != 0)
+                .position(|entry| entry.name() == Some(name))
+            {
+                return Some(((index as usize, slot), data[slot].clone()));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internals::tests::SAMPLE as BYTES;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn metadata_works() {
+        let fs = FileSystemReader::from(BYTES);
+
+        let canvas = fs.metadata("/canvas").unwrap();
+        assert_eq!(canvas.kind, FatKind::File);
+        assert_eq!(canvas.size, 56);
+        assert_eq!(
+            canvas.modified,
+            UNIX_EPOCH + Duration::from_secs(1567531938)
+        );
+
+        let layers = fs.metadata("/layers").unwrap();
+        assert_eq!(layers.kind, FatKind::Folder);
+
+        let layer = fs.metadata("/layers/00000002").unwrap();
+        assert_eq!(layer.kind, FatKind::File);
+
+        assert!(fs.metadata("/nothing").is_err());
+        assert!(fs.metadata("/canvas/nothing").is_err());
+    }
+}
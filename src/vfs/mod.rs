@@ -1,42 +1,112 @@
+mod metadata;
 mod reader;
 mod traverser;
+mod writer;
 
 pub(crate) use self::{reader::*, traverser::*};
 
-use crate::cipher::{DataBlock, TableBlock, VirtualPage, BLOCKS_PER_SECTOR, PAGE_SIZE};
+use crate::{
+    cipher::{
+        ChecksumMismatchError, DataBlock, FatEntry, TableBlock, TableEntry, VirtualPage,
+        BLOCKS_PER_SECTOR, PAGE_SIZE,
+    },
+    internals::sync::Lock,
+};
 use std::{
-    cell::RefCell,
     collections::HashMap,
     fs::File,
-    io::{BufReader, Cursor, Read, Seek},
+    io::{self, BufReader, Cursor, Read, Seek, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-pub(crate) trait ReadSeek: Read + Seek {}
+/// The index of the `DataBlock` that holds the entries of the root folder.
+pub(crate) const ROOT_INDEX: usize = 2;
+
+/// The location of a `FatEntry`: the index of the `DataBlock` that holds it, and its position
+/// inside of that block.
+type EntryLocation = (usize, usize);
+
+pub(crate) trait ReadSeek: Read + Seek + Send {}
 
 impl ReadSeek for File {}
-impl<T> ReadSeek for Cursor<T> where T: AsRef<[u8]> {}
+impl<T> ReadSeek for Cursor<T> where T: AsRef<[u8]> + Send {}
+
+/// A memory-mapped file, shared between all the readers that were cloned from the same one.
+#[cfg(feature = "mmap")]
+#[derive(Clone, Debug)]
+struct MappedFile(Arc<memmap2::Mmap>);
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Where the bytes read by a `FileSystemReader` come from.
+#[derive(Clone, Debug)]
+enum Source {
+    Bytes(Arc<[u8]>),
+    File(PathBuf),
+    #[cfg(feature = "mmap")]
+    Mmap(MappedFile),
+}
+
+impl Source {
+    /// # Panics
+    ///
+    /// If the file could not be opened.
+    fn open(&self) -> Box<dyn ReadSeek> {
+        match self {
+            Self::Bytes(bytes) => Box::new(Cursor::new(Arc::clone(bytes))),
+            Self::File(path) => Box::new(File::open(path).unwrap()),
+            #[cfg(feature = "mmap")]
+            Self::Mmap(mapped) => Box::new(Cursor::new(mapped.clone())),
+        }
+    }
+
+    /// The bytes of this source, if all of them can be accessed without reading them first.
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes),
+            Self::File(_) => None,
+            #[cfg(feature = "mmap")]
+            Self::Mmap(mapped) => Some(mapped.as_ref()),
+        }
+    }
+}
 
 /// # Interior Mutability
 ///
-/// All fields on `FileSystemReader` are wrapped on `Cell` like types.
+/// All fields on `FileSystemReader` that change while reading are wrapped on a [`Lock`].
 ///
-/// The major reason for that is, because I don't want to force the API to have &mut everywhere; If
-/// later on I want to make this type `thread-safe` it will be easier to do so. However, for now
-/// making it thread-safe will be quite complicated, since I don't know how you will be able to
-/// `seek` a stream between multiple threads without using something like a `Mutex`, but that would
-/// be counter-productive tbh.
+/// The major reason for that is, because I don't want to force the API to have &mut everywhere.
+/// By default, a `Lock` is just a `RefCell`, which means that anything having a
+/// `FileSystemReader` in it will not be `Sync`.
 ///
-/// With that restriction, that means that anything having a `FileSystemReader` in it will not be
-/// `Sync`.
+/// With the `parallel` feature, a `Lock` is a `Mutex` instead, so the reader can be shared
+/// between threads; however, only one of them can `seek` (and read) the stream at the same time,
+/// so the decryption and decompression of the blocks is the only part that actually runs in
+/// parallel.
 pub(crate) struct FileSystemReader {
     /// The reader holding the encrypted SAI file bytes.
-    bufreader: RefCell<BufReader<Box<dyn ReadSeek>>>,
+    bufreader: Lock<BufReader<Box<dyn ReadSeek>>>,
 
-    // TODO: Instead of caching _all_ the `TableEntry`s I could only cache _up to_ an X amount of
-    // them, and remove previous entries if that threshold is met.
-    //
     /// Cached `TableEntry`s.
-    table: RefCell<HashMap<usize, TableBlock>>,
+    table: Lock<HashMap<usize, TableBlock>>,
+    /// The maximum amount of `TableBlock`s that `table` can hold; [`None`] if unbounded.
+    table_capacity: Option<usize>,
+    /// The amount of blocks (of `PAGE_SIZE`) on the reader.
+    len: usize,
+    /// The amount of bytes after the last block, which are ignored.
+    trailing_bytes: usize,
+    /// Encrypted blocks that replace the ones from the reader; see [`FileSystemReader::write_data`].
+    written: HashMap<usize, VirtualPage>,
+    /// All the `DataBlock`s already decrypted, indexed by block (the ones of `TableBlock`s are
+    /// zeroed); see [`FileSystemReader::new_in_memory`].
+    decrypted: Option<Arc<Vec<DataBlock>>>,
+    source: Source,
 }
 
 impl FileSystemReader {
@@ -44,14 +114,17 @@ impl FileSystemReader {
 
     /// Creates a `FileSystemReader` without checking if all `SaiBlock`s inside are indeed valid.
     ///
-    /// The method will still check if `reader.stream_len()` is block aligned.
+    /// If `reader.stream_len()` is not block aligned ( not divisable by 4096; all sai blocks
+    /// should be 4096 ), the bytes after the last whole block are ignored; see
+    /// [`FileSystemReader::trailing_bytes`].
     ///
     /// # Panics
     ///
-    /// If the reader is not block aligned ( not divisable by 4096; all sai blocks should be 4096 ).
-    ///
     /// If at any moment, the `FileSystemReader` encounters an invalid `SaiBlock`.
-    pub(crate) fn new_unchecked(mut reader: impl ReadSeek + 'static) -> Self {
+    #[allow(clippy::cast_possible_truncation)]
+    fn new_unchecked(source: Source) -> Self {
+        let mut reader = source.open();
+
         // copied from stream_len() from std nightly.
         let stream_len = {
             let old_pos = reader.stream_position().unwrap();
@@ -61,81 +134,349 @@ impl FileSystemReader {
             len
         };
 
-        assert_eq!(
-            stream_len & 0x1FF,
-            0,
-            "the reader's bytes are not be block aligned."
-        );
-
         Self {
             // TODO: Benchmark what capacity will be okay to hold in memory.
             //
             // Caching a whole page could be OK-ish, but 2.09 MB seems a lot. I guess I could give
             // the option to users to set what amount of memory this.
-            bufreader: RefCell::new(BufReader::with_capacity(PAGE_SIZE * 2, Box::new(reader))),
+            bufreader: Lock::new(BufReader::with_capacity(PAGE_SIZE * 2, reader)),
             table: HashMap::new().into(),
+            table_capacity: None,
+            len: stream_len as usize / PAGE_SIZE,
+            trailing_bytes: stream_len as usize % PAGE_SIZE,
+            written: HashMap::new(),
+            decrypted: None,
+            source,
+        }
+    }
+
+    /// Same as [`FileSystemReader::new_unchecked`], but decrypts all the blocks of `bytes` up
+    /// front, so reading a `DataBlock` is just a copy of it.
+    ///
+    /// This trades memory (a second copy of the file) for speed, since no block is decrypted
+    /// more than once, and there is no reader that needs to be seeked (nor locked).
+    ///
+    /// # Panics
+    ///
+    /// If the sai file is corrupted ( checksums doesn't match ).
+    pub(crate) fn new_in_memory(bytes: Vec<u8>) -> Self {
+        let mut fs = Self::new_unchecked(Source::Bytes(bytes.into()));
+
+        let decrypted = (0..fs.len)
+            .map(|index| {
+                if index.is_multiple_of(BLOCKS_PER_SECTOR) {
+                    DataBlock::from(VirtualPage::from([0; PAGE_SIZE]))
+                } else {
+                    fs.read_data(index).0
+                }
+            })
+            .collect();
+        fs.decrypted = Some(Arc::new(decrypted));
+
+        fs
+    }
+
+    /// Same as [`FileSystemReader::new_unchecked`], but reads the file found at `path`.
+    ///
+    /// # Panics
+    ///
+    /// If the file could not be opened; see also [`FileSystemReader::new_unchecked`].
+    pub(crate) fn open_unchecked(path: impl AsRef<Path>) -> Self {
+        Self::new_unchecked(Source::File(path.as_ref().to_path_buf()))
+    }
+
+    /// Same as [`FileSystemReader::open_unchecked`], but only keeps up to `table_capacity`
+    /// `TableBlock`s cached at the same time.
+    ///
+    /// # Panics
+    ///
+    /// If `table_capacity` is `0`; see also [`FileSystemReader::open_unchecked`].
+    pub(crate) fn open_bounded_unchecked(path: impl AsRef<Path>, table_capacity: usize) -> Self {
+        assert_ne!(table_capacity, 0, "at least one table needs to be cached");
+
+        Self {
+            table_capacity: Some(table_capacity),
+            ..Self::open_unchecked(path)
         }
     }
 
-    // TODO: `seek()` is not used for now.
-    //
-    // I'm thinking of providing a `feature` that would allow the user to load the `whole` sai file
-    // on memory, then decrypt the file buffer, then store it here instead of using an `BufReader`.
-    //
-    // That should increase the performance of the reader as a whole ( concurrent reads will be
-    // posible ), but with the drawback of high memory usage, and some API changes.
-    //
-    // Before implementing all of that, I want to finish the v.0.2.0 to see if there is a *big*
-    // advantage of doing that.
-
-    // fn seek(&self, offset: u64) -> u64 {
-    //     // TODO: Handle `Result`.
-    //     self.bufreader
-    //         .borrow_mut()
-    //         .seek_relative(offset as i64)
-    //         .unwrap();
-    //
-    //     self.bufreader.borrow_mut().stream_position().unwrap()
-    // }
+    /// Same as [`FileSystemReader::open_unchecked`], but maps the file found at `path` to memory,
+    /// instead of reading it through a buffer.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified (by this, or any other process) while it is mapped; see
+    /// [`memmap2::Mmap::map`].
+    ///
+    /// # Panics
+    ///
+    /// If the file could not be opened, or mapped; see also
+    /// [`FileSystemReader::open_unchecked`].
+    #[cfg(feature = "mmap")]
+    pub(crate) unsafe fn open_mmap_unchecked(path: impl AsRef<Path>) -> Self {
+        let file = File::open(path).unwrap();
+        // SAFETY: Ensured by the caller.
+        let mmap = unsafe { memmap2::Mmap::map(&file) }.unwrap();
+
+        Self::new_unchecked(Source::Mmap(MappedFile(Arc::new(mmap))))
+    }
+
+    /// The amount of bytes found after the last block ( usually appended by other tools ), which
+    /// are ignored by the reader.
+    pub(crate) fn trailing_bytes(&self) -> usize {
+        self.trailing_bytes
+    }
+
+    /// The amount of `TableBlock`s that are currently cached.
+    #[cfg(test)]
+    pub(crate) fn cached_tables(&self) -> usize {
+        self.table.lock().len()
+    }
 
     // TODO: Remove unwraps
     /// Gets the `SaiBlock`'s bytes at the specified `index`.
+    #[allow(clippy::cast_possible_wrap)]
     fn read_block(&self, index: usize) -> VirtualPage {
-        let mut reader = self.bufreader.borrow_mut();
+        if let Some(page) = self.written.get(&index) {
+            return page.clone();
+        }
+
+        // No need to seek (nor lock) the reader if the bytes are already on memory.
+        if let Some(bytes) = self.source.as_bytes() {
+            let block: [u8; PAGE_SIZE] = bytes[index * PAGE_SIZE..][..PAGE_SIZE]
+                .try_into()
+                .expect("slice is PAGE_SIZE long");
+            return block.into();
+        }
+
+        let mut reader = self.bufreader.lock();
 
         let position = reader.stream_position().unwrap();
         let offset = (index * PAGE_SIZE) as i64 - position as i64;
         reader.seek_relative(offset).unwrap();
 
         let mut block = [0; PAGE_SIZE];
-        reader.read(&mut block).unwrap();
+        reader.read_exact(&mut block).unwrap();
 
         block.into()
     }
 
     /// Gets the `DataBlock` at the specified `index`.
     ///
+    /// Unused blocks ( the checksum of their `TableEntry` is `0` ) are read as if they were
+    /// zeroed; i.e: an unused root block is an empty root folder.
+    ///
     /// # Panics
     ///
     /// If the sai file is corrupted ( checksums doesn't match ).
     pub(crate) fn read_data(&self, index: usize) -> (DataBlock, Option<u32>) {
-        debug_assert!(index % BLOCKS_PER_SECTOR != 0);
+        let entry = self.table_entry(index);
+
+        let data = if let Some(ref decrypted) = self.decrypted {
+            decrypted[index].clone()
+        } else if entry.checksum() == 0 {
+            DataBlock::from(VirtualPage::from([0; PAGE_SIZE]))
+        } else {
+            DataBlock::decrypt(self.read_block(index), entry.checksum())
+                .expect("sai file is corrupted")
+        };
+
+        (
+            data,
+            (entry.next_block() != 0).then_some(entry.next_block()),
+        )
+    }
+
+    /// Whether the root block is unused, so the root folder doesn't have any entries; see
+    /// [`FileSystemReader::read_data`].
+    pub(crate) fn root_is_unused(&self) -> bool {
+        self.table_entry(ROOT_INDEX).checksum() == 0
+    }
+
+    /// Gets the `TableEntry` that belongs to the `DataBlock` at the specified `index`.
+    ///
+    /// # Panics
+    ///
+    /// If the sai file is corrupted ( checksums doesn't match ).
+    #[allow(clippy::cast_possible_truncation)]
+    fn table_entry(&self, index: usize) -> TableEntry {
+        debug_assert!(!index.is_multiple_of(BLOCKS_PER_SECTOR));
 
         let table_index = index & !0x1FF;
 
-        let mut table = self.table.borrow_mut();
+        let mut table = self.table.lock();
+        if let Some(capacity) = self.table_capacity {
+            if table.len() >= capacity && !table.contains_key(&table_index) {
+                // Any table can be evicted, since reading it again is cheap.
+                let evicted = *table.keys().next().expect("capacity is not 0");
+                table.remove(&evicted);
+            }
+        }
         let table = table.entry(table_index).or_insert_with(|| {
             TableBlock::decrypt(self.read_block(table_index), table_index as u32)
                 .expect("sai file is corrupted")
         });
 
-        let entry = &table[index % BLOCKS_PER_SECTOR];
+        table[index % BLOCKS_PER_SECTOR].clone()
+    }
 
-        (
-            DataBlock::decrypt(self.read_block(index), entry.checksum())
-                .expect("sai file is corrupted"),
-            (entry.next_block() != 0).then_some(entry.next_block()),
-        )
+    /// Replaces the contents of the `DataBlock` at the specified `index` with `page` (decrypted),
+    /// updating the checksum of its `TableEntry`.
+    ///
+    /// The underlying reader is never modified; see [`FileSystemReader::write_to`].
+    ///
+    /// # Panics
+    ///
+    /// If the sai file is corrupted ( checksums doesn't match ).
+    pub(crate) fn write_data(&mut self, index: usize, page: VirtualPage) {
+        let block = DataBlock::from(page);
+        let checksum = block.checksum();
+        self.set_decrypted(index, &block);
+        self.written.insert(index, block.encrypt(Some(checksum)));
+        self.update_table_entry(index, |entry| entry.set_checksum(checksum));
+    }
+
+    /// Adds `page` (decrypted) as a new `DataBlock` after the last block, returning its index.
+    ///
+    /// If `prev` is given, the new block is chained after the block at that index (which should
+    /// be the last block of a file). If the last `TableBlock` is full, a new one is added before
+    /// the new block.
+    ///
+    /// Like [`FileSystemReader::write_data`], the underlying reader is never modified.
+    ///
+    /// # Panics
+    ///
+    /// If the sai file is corrupted ( checksums doesn't match ).
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn append_data(&mut self, page: VirtualPage, prev: Option<usize>) -> usize {
+        if self.len.is_multiple_of(BLOCKS_PER_SECTOR) {
+            let table_index = self.len;
+            let table = TableBlock::from(VirtualPage::from([0; PAGE_SIZE]));
+            self.written
+                .insert(table_index, table.clone().encrypt(table_index as u32));
+            self.table.lock().insert(table_index, table);
+            self.len += 1;
+        }
+
+        let index = self.len;
+        self.len += 1;
+
+        let block = DataBlock::from(page);
+        let checksum = block.checksum();
+        self.set_decrypted(index, &block);
+        self.written.insert(index, block.encrypt(Some(checksum)));
+        // The entry could have leftovers of a previous block.
+        self.update_table_entry(index, |entry| {
+            entry.set_checksum(checksum);
+            entry.set_next_block(0);
+        });
+
+        if let Some(prev) = prev {
+            self.update_table_entry(prev, |entry| entry.set_next_block(index as u32));
+        }
+
+        index
+    }
+
+    /// Replaces the decrypted `DataBlock` at the specified `index`, if the blocks were decrypted
+    /// up front; see [`FileSystemReader::new_in_memory`].
+    fn set_decrypted(&mut self, index: usize, block: &DataBlock) {
+        if let Some(ref mut decrypted) = self.decrypted {
+            let decrypted = Arc::make_mut(decrypted);
+            if decrypted.len() <= index {
+                decrypted.resize(
+                    index + 1,
+                    DataBlock::from(VirtualPage::from([0; PAGE_SIZE])),
+                );
+            }
+            decrypted[index] = block.clone();
+        }
+    }
+
+    /// Calls `f` with the `TableEntry` of the block at the specified `index`, re-encrypting the
+    /// `TableBlock` that it belongs to.
+    #[allow(clippy::cast_possible_truncation)]
+    fn update_table_entry(&mut self, index: usize, f: impl FnOnce(&mut TableEntry)) {
+        let _ = self.table_entry(index);
+
+        let table_index = index & !0x1FF;
+        let mut tables = self.table.lock();
+        let table = tables.get_mut(&table_index).expect("table was cached");
+        f(&mut table[index % BLOCKS_PER_SECTOR]);
+        self.written
+            .insert(table_index, table.clone().encrypt(table_index as u32));
+    }
+
+    /// Writes all the (encrypted) blocks, including the ones replaced by
+    /// [`FileSystemReader::write_data`].
+    pub(crate) fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        for index in 0..self.len {
+            writer.write_all(&*self.read_block(index))?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets the `TableEntry`s of all the blocks (including the `TableBlock`s themselves), ordered
+    /// by their index.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn table_entries(&self) -> io::Result<Vec<TableEntry>> {
+        let mut entries = Vec::with_capacity(self.len);
+        for table_index in (0..self.len).step_by(BLOCKS_PER_SECTOR) {
+            let table = TableBlock::decrypt(self.read_block(table_index), table_index as u32)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let blocks = (self.len - table_index).min(BLOCKS_PER_SECTOR);
+            entries.extend_from_slice(&table[..blocks]);
+        }
+
+        Ok(entries)
+    }
+
+    /// Decrypts every block, collecting the index of the ones whose checksum doesn't match.
+    ///
+    /// The `DataBlock`s of a `TableBlock` that doesn't match can't be checked, so they are
+    /// skipped; unused blocks ( checksum `0` ) are skipped too.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn verify_blocks(&self) -> Vec<(usize, ChecksumMismatchError)> {
+        let mut mismatches = Vec::new();
+        for table_index in (0..self.len).step_by(BLOCKS_PER_SECTOR) {
+            let table = match TableBlock::decrypt(self.read_block(table_index), table_index as u32)
+            {
+                Ok(table) => table,
+                Err(err) => {
+                    mismatches.push((table_index, err));
+                    continue;
+                }
+            };
+
+            let blocks = (self.len - table_index).min(BLOCKS_PER_SECTOR);
+            for (offset, entry) in table[..blocks].iter().enumerate().skip(1) {
+                if entry.checksum() == 0 {
+                    continue;
+                }
+
+                let index = table_index + offset;
+                if let Err(err) = DataBlock::decrypt(self.read_block(index), entry.checksum()) {
+                    mismatches.push((index, err));
+                }
+            }
+        }
+
+        mismatches
+    }
+
+    /// Gets the checksums of all the `DataBlock`s that hold the contents of `entry`, without
+    /// decrypting them.
+    pub(crate) fn block_checksums(&self, entry: &FatEntry) -> Vec<u32> {
+        let mut checksums = Vec::new();
+        let mut next_index = Some(entry.next_block());
+        while let Some(index) = next_index {
+            let entry = self.table_entry(index as usize);
+            checksums.push(entry.checksum());
+            next_index = (entry.next_block() != 0).then_some(entry.next_block());
+        }
+
+        checksums
     }
 }
 
@@ -149,8 +490,87 @@ impl From<&[u8]> for FileSystemReader {
 
 impl From<Vec<u8>> for FileSystemReader {
     fn from(bytes: Vec<u8>) -> Self {
-        let cursor = Cursor::new(bytes);
+        Self::new_unchecked(Source::Bytes(bytes.into()))
+    }
+}
+
+impl Clone for FileSystemReader {
+    /// Creates a new reader over the same bytes, keeping the blocks written with
+    /// [`FileSystemReader::write_data`].
+    ///
+    /// If the bytes come from a file, the file is opened again; unless it was memory-mapped, in
+    /// which case the mapping is shared.
+    ///
+    /// # Panics
+    ///
+    /// If the file could not be opened again.
+    fn clone(&self) -> Self {
+        let mut fs = Self::new_unchecked(self.source.clone());
+        fs.table_capacity = self.table_capacity;
+        fs.written.clone_from(&self.written);
+        fs.decrypted.clone_from(&self.decrypted);
+        fs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internals::tests::SAMPLE as BYTES;
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn append_data_works() -> io::Result<()> {
+        let mut fs = FileSystemReader::from(BYTES);
+        let len = fs.len;
+
+        let page = |byte: u8| VirtualPage::from([byte; PAGE_SIZE]);
+        let first = fs.append_data(page(1), None);
+        let second = fs.append_data(page(2), Some(first));
+
+        assert_eq!((first, second), (len, len + 1));
+
+        let mut bytes = Vec::new();
+        fs.write_to(&mut bytes)?;
+        assert_eq!(bytes.len(), (len + 2) * PAGE_SIZE);
+
+        for fs in [&fs, &FileSystemReader::from(bytes)] {
+            let (data, next_block) = fs.read_data(first);
+            assert_eq!(*data.into_virtual_page(), *page(1));
+            assert_eq!(next_block, Some(second as u32));
+
+            let (data, next_block) = fs.read_data(second);
+            assert_eq!(*data.into_virtual_page(), *page(2));
+            assert_eq!(next_block, None);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn append_data_adds_table_blocks() -> io::Result<()> {
+        let mut fs = FileSystemReader::from(BYTES);
+
+        let mut prev = None;
+        while fs.len <= BLOCKS_PER_SECTOR * 2 {
+            prev = Some(fs.append_data(VirtualPage::from([3; PAGE_SIZE]), prev));
+        }
+
+        let mut bytes = Vec::new();
+        fs.write_to(&mut bytes)?;
+        let fs = FileSystemReader::from(bytes);
+
+        // Every table block is valid, and the chain skips over them.
+        let entries = fs.table_entries()?;
+        let table_index = BLOCKS_PER_SECTOR * 2;
+        assert_eq!(
+            entries[table_index - 1].next_block(),
+            table_index as u32 + 1
+        );
+        assert_eq!(entries[table_index + 1].next_block(), 0);
+        assert_eq!(fs.read_data(table_index + 1).1, None);
 
-        Self::new_unchecked(cursor)
+        Ok(())
     }
 }
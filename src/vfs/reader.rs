@@ -25,6 +25,7 @@ impl<'a> FatEntryReader<'a> {
 
 impl Read for FatEntryReader<'_> {
     // This implemenation always behaves like `read_exact()`.
+    #[allow(clippy::cast_possible_truncation)]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let len = buf.len();
         let mut left_to_read = len;
@@ -50,6 +51,12 @@ impl Read for FatEntryReader<'_> {
 
                     self.cursor = None;
                     left_to_read -= bytes.len();
+
+                    // Don't go to the next block if `buf` ends at the block boundary; it could be
+                    // the last one.
+                    if left_to_read == 0 {
+                        break;
+                    }
                 } else {
                     let mut bytes = vec![0; left_to_read];
                     reader.read_exact(&mut bytes)?;
@@ -75,3 +82,60 @@ impl Read for FatEntryReader<'_> {
         self.read(buf).map(|_| ())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{internals::tests::SAMPLE as BYTES, vfs::FsTraverser};
+
+    /// Gets the entry of the only layer of `toobig.sai`, which spans across multiple blocks.
+    fn layer_entry(fs: &FileSystemReader) -> FatEntry {
+        fs.traverse_root(|_, entry| entry.name() == Some("00000002"))
+            .expect("layer exists")
+    }
+
+    #[test]
+    fn reader_follows_next_block() -> io::Result<()> {
+        let fs = FileSystemReader::from(BYTES);
+        let entry = layer_entry(&fs);
+        let blocks = fs.block_checksums(&entry).len();
+
+        assert!(blocks > 1);
+        assert!(entry.size() as usize > PAGE_SIZE);
+
+        let mut expected = vec![0; entry.size() as usize];
+        FatEntryReader::new(&fs, &entry).read_exact(&mut expected)?;
+
+        // Chunks that are not block aligned, so every read straddles a block boundary.
+        let mut actual = Vec::with_capacity(expected.len());
+        let mut reader = FatEntryReader::new(&fs, &entry);
+        for chunk in expected.chunks(PAGE_SIZE - 1) {
+            let mut bytes = vec![0; chunk.len()];
+            reader.read_exact(&mut bytes)?;
+            actual.extend_from_slice(&bytes);
+        }
+
+        assert!(actual == expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reader_reads_until_last_block_boundary() -> io::Result<()> {
+        let fs = FileSystemReader::from(BYTES);
+        let entry = layer_entry(&fs);
+        let blocks = fs.block_checksums(&entry).len();
+
+        let mut reader = FatEntryReader::new(&fs, &entry);
+        for _ in 0..blocks {
+            reader.read_exact(&mut [0; PAGE_SIZE])?;
+        }
+
+        assert_eq!(
+            reader.read_exact(&mut [0; 1]).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        Ok(())
+    }
+}
@@ -3,9 +3,10 @@
 // the file format, I will work it latter on.
 
 use crate::{
-    cipher::{FatEntry, FatKind},
-    vfs::FileSystemReader,
+    cipher::{DataBlock, FatEntry, FatKind},
+    vfs::{FileSystemReader, ROOT_INDEX},
 };
+use std::io;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum TraverseEvent {
@@ -33,7 +34,81 @@ impl FsTraverser for FileSystemReader {
         &self,
         on_traverse: impl Fn(TraverseEvent, &FatEntry) -> bool,
     ) -> Option<FatEntry> {
-        traverse_data(self, 2, &on_traverse)
+        traverse_data(self, ROOT_INDEX, &on_traverse)
+    }
+}
+
+impl FileSystemReader {
+    /// Iterates over every file and folder entry of the file system, depth-first; the entries of a
+    /// folder come right after the folder itself.
+    pub(crate) fn entries(&self) -> Entries<'_> {
+        Entries {
+            fs: self,
+            stack: vec![Self::folder_cursor(self.read_data(ROOT_INDEX))],
+        }
+    }
+
+    fn folder_cursor((data, next_block): (DataBlock, Option<u32>)) -> FolderCursor {
+        FolderCursor {
+            data,
+            next_block,
+            slot: 0,
+        }
+    }
+}
+
+/// The position of an [`Entries`] iterator inside of a folder.
+struct FolderCursor {
+    data: DataBlock,
+    next_block: Option<u32>,
+    slot: usize,
+}
+
+/// Iterator over all `FatEntry`s of a file system; see [`FileSystemReader::entries`].
+pub(crate) struct Entries<'a> {
+    fs: &'a FileSystemReader,
+    /// The folders that are currently being iterated, from the root to the deepest one.
+    stack: Vec<FolderCursor>,
+}
+
+impl Iterator for Entries<'_> {
+    type Item = io::Result<FatEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let cursor = self.stack.last_mut()?;
+
+            let Some(entry) = cursor.data.get(cursor.slot).filter(|e| e.flags() != 0) else {
+                match cursor.next_block {
+                    Some(index) => {
+                        *cursor =
+                            FileSystemReader::folder_cursor(self.fs.read_data(index as usize));
+                    }
+                    None => _ = self.stack.pop(),
+                }
+                continue;
+            };
+            let entry = entry.to_owned();
+            cursor.slot += 1;
+
+            match entry.kind() {
+                Some(FatKind::File) => {}
+                Some(FatKind::Folder) => {
+                    let folder = self.fs.read_data(entry.next_block() as usize);
+                    self.stack.push(FileSystemReader::folder_cursor(folder));
+                }
+                None => {
+                    // Without a valid kind, there is no way to know where the next entries are.
+                    self.stack.clear();
+                    return Some(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{:?} entry has an unknown kind", entry.name()),
+                    )));
+                }
+            }
+
+            return Some(Ok(entry));
+        }
     }
 }
 
@@ -62,13 +137,16 @@ fn traverse_data(
                 FatKind::Folder => {
                     if on_traverse(TraverseEvent::FolderStart, entry) {
                         return Some(entry.to_owned());
-                    };
+                    }
 
-                    traverse_data(fs, entry.next_block() as usize, on_traverse);
+                    if let Some(entry) = traverse_data(fs, entry.next_block() as usize, on_traverse)
+                    {
+                        return Some(entry);
+                    }
 
                     if on_traverse(TraverseEvent::FolderEnd, entry) {
                         return Some(entry.to_owned());
-                    };
+                    }
                 }
             }
         }
@@ -88,14 +166,13 @@ mod tests {
     use std::{
         cell::{Cell, RefCell},
         fmt::Display,
-        io,
     };
     // TODO: This is useless ('<', '^', '>'). std formatter can _apparently_ already do it.
     use tabular::{Row, Table};
 
     #[test]
     // Cool tree view of the underlying sai file system. Keeping it here to make sure the file is being read correctly :).
-    fn traverser_works() -> io::Result<()> {
+    fn traverser_works() {
         struct TreeVisitor {
             depth: Cell<usize>,
             table: RefCell<Table>,
@@ -112,13 +189,14 @@ mod tests {
                     TraverseEvent::FolderEnd => {
                         self.depth.set(self.depth.get() - 1);
                     }
-                };
+                }
 
                 false
             }
 
+            #[allow(clippy::cast_possible_wrap)]
             fn add_row(&self, entry: &FatEntry) {
-                let date = chrono::NaiveDateTime::from_timestamp_opt(entry.unixtime() as i64, 0)
+                let date = chrono::DateTime::from_timestamp(entry.unixtime() as i64, 0)
                     .expect("timestamp is not out-of-bounds.")
                     .format("%Y-%m-%d");
 
@@ -164,14 +242,36 @@ mod tests {
 
         assert_eq!(
             format!("\n{visitor}"),
-            r#"
+            r"
      32 f 2019-09-03 .73851dcd1203b24d
      56 f 2019-09-03 canvas
      12 f 2019-09-03 laytbl
         d 2019-09-03 layers/
 2404129 f 2019-09-03  00000002
   78412 f 2019-09-03 thumbnail
-"#
+"
+        );
+    }
+
+    #[test]
+    fn entries_works() -> io::Result<()> {
+        let fs = FileSystemReader::from(BYTES);
+
+        let names = fs
+            .entries()
+            .map(|entry| entry.map(|entry| entry.name().unwrap().to_owned()))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        assert_eq!(
+            names,
+            [
+                ".73851dcd1203b24d",
+                "canvas",
+                "laytbl",
+                "layers",
+                "00000002",
+                "thumbnail"
+            ]
         );
 
         Ok(())
@@ -186,4 +286,14 @@ mod tests {
 
         assert_eq!(actual.unwrap().name().unwrap(), EXPECTED_ENTRY_NAME);
     }
+
+    #[test]
+    fn traverser_returns_nested_entry() {
+        const EXPECTED_ENTRY_NAME: &str = "00000002";
+
+        let actual = FileSystemReader::from(BYTES)
+            .traverse_root(|_, entry| entry.name().is_some_and(|name| name == EXPECTED_ENTRY_NAME));
+
+        assert_eq!(actual.unwrap().name().unwrap(), EXPECTED_ENTRY_NAME);
+    }
 }
@@ -0,0 +1,362 @@
+use crate::{
+    cipher::{FatEntry, FatKind, PAGE_SIZE},
+    internals::time,
+    vfs::{EntryLocation, FileSystemReader, ROOT_INDEX},
+};
+use std::{
+    io, mem,
+    path::{Component, Path},
+};
+
+impl FileSystemReader {
+    /// Writes `bytes` as the contents of the file found at `path` (i.e: `/layers/00000002`),
+    /// creating the file if it doesn't exist yet.
+    ///
+    /// The blocks that already hold the file are reused; if more are needed they are added with
+    /// [`FileSystemReader::append_data`], and if less are needed the chain is cut short (the
+    /// leftover blocks are kept, but nothing points to them anymore).
+    ///
+    /// Like [`FileSystemReader::write_data`], the underlying reader is never modified.
+    ///
+    /// # Errors
+    ///
+    /// - If any of the folders on `path` doesn't exist.
+    /// - If `path` points to a folder.
+    /// - If the file name is longer than 31 bytes, or if `bytes` is bigger than 4 GiB.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn write_file(&mut self, path: impl AsRef<Path>, bytes: &[u8]) -> io::Result<()> {
+        let path = path.as_ref();
+        let invalid_input = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.len() < 32)
+            .ok_or_else(|| invalid_input(format!("{} is not a valid file", path.display())))?;
+        let size = u32::try_from(bytes.len())
+            .map_err(|_| invalid_input(format!("{} is too big", path.display())))?;
+
+        let (folder, parent) = self.find_folder(path.parent().unwrap_or(path))?;
+        let location = match self.find_entry(folder, name) {
+            Some((_, entry)) if entry.kind() != Some(FatKind::File) => {
+                return Err(invalid_input(format!("{} is not a file", path.display())));
+            }
+            Some((location, _)) => location,
+            None => self.insert_entry(folder, parent, FatEntry::new(name, FatKind::File, 0)),
+        };
+
+        let (data, _) = self.read_data(location.0);
+        let chain = match data[location.1].next_block() {
+            0 => Vec::new(),
+            index => self.chain(index as usize),
+        };
+
+        // Empty files still own a block.
+        let blocks = bytes.len().div_ceil(PAGE_SIZE).max(1);
+        let mut indices: Vec<usize> = Vec::with_capacity(blocks);
+        for block in 0..blocks {
+            let chunk = &bytes[(block * PAGE_SIZE).min(bytes.len())..];
+            let chunk = &chunk[..chunk.len().min(PAGE_SIZE)];
+            let mut page = [0; PAGE_SIZE];
+            page[..chunk.len()].copy_from_slice(chunk);
+
+            let index = if let Some(&index) = chain.get(block) {
+                self.write_data(index, page.into());
+                index
+            } else {
+                self.append_data(page.into(), indices.last().copied())
+            };
+            indices.push(index);
+        }
+        if chain.len() > blocks {
+            self.update_table_entry(indices[blocks - 1], |entry| entry.set_next_block(0));
+        }
+
+        self.update_fat_entry(location, |entry| {
+            entry.set_contents(indices[0] as u32, size);
+            entry.set_filetime(time::filetime_now());
+        });
+
+        Ok(())
+    }
+
+    /// Creates an empty folder at `path` (i.e: `/layers`).
+    ///
+    /// Like [`FileSystemReader::write_data`], the underlying reader is never modified.
+    ///
+    /// # Errors
+    ///
+    /// - If any of the folders on `path` (besides the last one) doesn't exist.
+    /// - If there is already an entry at `path`.
+    /// - If the folder name is longer than 31 bytes.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn create_dir(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.len() < 32)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a valid folder", path.display()),
+                )
+            })?;
+
+        let (folder, parent) = self.find_folder(path.parent().unwrap_or(path))?;
+        if self.find_entry(folder, name).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+
+        // Folders always own a block, even if they are empty.
+        let index = self.append_data([0; PAGE_SIZE].into(), None);
+        let mut entry = FatEntry::new(name, FatKind::Folder, time::filetime_now());
+        entry.set_contents(index as u32, 0);
+        self.insert_entry(folder, parent, entry);
+
+        Ok(())
+    }
+
+    /// Gets the index of the first block of the folder found at `path`, and the location of its
+    /// entry ( [`None`] for the root folder ).
+    fn find_folder(&self, path: &Path) -> io::Result<(usize, Option<EntryLocation>)> {
+        let not_found = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} folder was not found", path.display()),
+            )
+        };
+
+        let mut folder = (ROOT_INDEX, None);
+        for component in path.components() {
+            let name = match component {
+                Component::RootDir => continue,
+                Component::Normal(name) => name.to_str().ok_or_else(not_found)?,
+                _ => return Err(not_found()),
+            };
+
+            folder = match self.find_entry(folder.0, name) {
+                Some((location, entry)) if entry.kind() == Some(FatKind::Folder) => {
+                    (entry.next_block() as usize, Some(location))
+                }
+                _ => return Err(not_found()),
+            };
+        }
+
+        Ok(folder)
+    }
+
+    /// Adds `entry` to the first unused slot of the folder that starts at `index`, chaining a new
+    /// block to the folder if all of them are used.
+    ///
+    /// `parent` is the location of the entry of the folder itself, whose size is updated.
+    #[allow(clippy::cast_possible_truncation)]
+    fn insert_entry(
+        &mut self,
+        index: usize,
+        parent: Option<EntryLocation>,
+        entry: FatEntry,
+    ) -> EntryLocation {
+        let chain = self.chain(index);
+        let unused = chain.iter().find_map(|&index| {
+            let (data, _) = self.read_data(index);
+            data.iter()
+                .position(|entry| entry.flags() == 0)
+                .map(|slot| (index, slot))
+        });
+        let location = unused.unwrap_or_else(|| {
+            let last = *chain.last().expect("chain is not empty");
+            (self.append_data([0; PAGE_SIZE].into(), Some(last)), 0)
+        });
+
+        self.update_fat_entry(location, |slot| *slot = entry);
+        if let Some(parent) = parent {
+            self.update_fat_entry(parent, |folder| {
+                let size = folder.size() + mem::size_of::<FatEntry>() as u32;
+                folder.set_contents(folder.next_block(), size);
+            });
+        }
+
+        location
+    }
+
+    /// Calls `f` with the `FatEntry` found at `location`, re-encrypting the block that holds it.
+    fn update_fat_entry(&mut self, (index, slot): EntryLocation, f: impl FnOnce(&mut FatEntry)) {
+        let (mut data, _) = self.read_data(index);
+        f(&mut data[slot]);
+        self.write_data(index, data.into_virtual_page());
+    }
+
+    /// Gets the indices of the blocks chained by their `TableEntry::next_block`, starting at
+    /// `index`.
+    fn chain(&self, index: usize) -> Vec<usize> {
+        let mut indices = Vec::new();
+        let mut next_index = Some(index);
+        while let Some(index) = next_index {
+            indices.push(index);
+            let next_block = self.table_entry(index).next_block();
+            next_index = (next_block != 0).then_some(next_block as usize);
+        }
+
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        internals::tests::SAMPLE as BYTES,
+        vfs::{FatEntryReader, FsTraverser, TraverseEvent},
+    };
+    use std::{cell::RefCell, io::Read};
+
+    /// Reads the whole contents of the file found at `path`.
+    fn read_file(fs: &FileSystemReader, path: &str) -> io::Result<Vec<u8>> {
+        let name = path.rsplit('/').next().unwrap();
+        let entry = fs
+            .traverse_root(|_, entry| entry.name() == Some(name))
+            .expect("file exists");
+
+        let mut bytes = vec![0; entry.size() as usize];
+        FatEntryReader::new(fs, &entry).read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn round_trip(fs: &FileSystemReader) -> io::Result<FileSystemReader> {
+        let mut bytes = Vec::new();
+        fs.write_to(&mut bytes)?;
+        Ok(FileSystemReader::from(bytes))
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_file_grows_and_shrinks_files() -> io::Result<()> {
+        let mut fs = FileSystemReader::from(BYTES);
+
+        // `canvas` only has a single block.
+        let bigger = (0..PAGE_SIZE * 3 + 10).map(|i| i as u8).collect::<Vec<_>>();
+        fs.write_file("/canvas", &bigger)?;
+        // `00000002` has a lot of blocks.
+        fs.write_file("/layers/00000002", b"smaller")?;
+
+        for fs in [&fs, &round_trip(&fs)?] {
+            assert_eq!(read_file(fs, "/canvas")?, bigger);
+            assert_eq!(fs.metadata("/canvas")?.size, bigger.len() as u32);
+            assert_eq!(read_file(fs, "/layers/00000002")?, b"smaller");
+
+            let (_, layer) = fs
+                .find_entry(fs.find_folder(Path::new("/layers"))?.0, "00000002")
+                .unwrap();
+            assert_eq!(fs.chain(layer.next_block() as usize).len(), 1);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_file_creates_files() -> io::Result<()> {
+        let mut fs = FileSystemReader::from(BYTES);
+        let folder_size = fs.metadata("/layers")?.size;
+
+        fs.write_file("/layers/00000003", b"new")?;
+
+        for fs in [&fs, &round_trip(&fs)?] {
+            assert_eq!(read_file(fs, "/layers/00000003")?, b"new");
+            assert_eq!(read_file(fs, "/layers/00000002")?.len(), 2404129);
+            assert_eq!(fs.metadata("/layers")?.size, folder_size + 64);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn write_file_chains_folder_blocks() -> io::Result<()> {
+        let mut fs = FileSystemReader::from(BYTES);
+
+        // A folder block holds 64 entries.
+        for id in 0..70 {
+            fs.write_file(format!("/layers/file{id}"), &[id as u8])?;
+        }
+
+        let fs = round_trip(&fs)?;
+        let (folder, _) = fs.find_folder(Path::new("/layers"))?;
+        assert_eq!(fs.chain(folder).len(), 2);
+        assert_eq!(read_file(&fs, "/layers/file69")?, [69]);
+        assert_eq!(fs.metadata("/layers")?.size, 71 * 64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_dir_works() -> io::Result<()> {
+        let mut fs = FileSystemReader::from(BYTES);
+
+        fs.create_dir("/sublayers")?;
+        fs.create_dir("/sublayers/nested")?;
+        fs.write_file("/sublayers/nested/file", b"nested")?;
+
+        for fs in [&fs, &round_trip(&fs)?] {
+            let sublayers = fs.metadata("/sublayers")?;
+            assert_eq!(sublayers.kind, FatKind::Folder);
+            assert_eq!(sublayers.size, 64);
+            assert_eq!(fs.metadata("/sublayers/nested")?.kind, FatKind::Folder);
+            assert_eq!(read_file(fs, "/sublayers/nested/file")?, b"nested");
+
+            let events = RefCell::new(Vec::new());
+            fs.traverse_root(|event, entry| {
+                if entry.name() != Some("layers") {
+                    events
+                        .borrow_mut()
+                        .push((event, entry.name().unwrap().to_owned()));
+                }
+                false
+            });
+            assert!(events.into_inner().ends_with(&[
+                (TraverseEvent::FolderStart, "sublayers".into()),
+                (TraverseEvent::FolderStart, "nested".into()),
+                (TraverseEvent::File, "file".into()),
+                (TraverseEvent::FolderEnd, "nested".into()),
+                (TraverseEvent::FolderEnd, "sublayers".into()),
+            ]));
+        }
+
+        let kind = |result: io::Result<()>| result.unwrap_err().kind();
+        assert_eq!(
+            kind(fs.create_dir("/sublayers")),
+            io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(kind(fs.create_dir("/canvas")), io::ErrorKind::AlreadyExists);
+        assert_eq!(kind(fs.create_dir("/nothing/dir")), io::ErrorKind::NotFound);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_file_errors() {
+        let mut fs = FileSystemReader::from(BYTES);
+
+        let kind = |result: io::Result<()>| result.unwrap_err().kind();
+        assert_eq!(
+            kind(fs.write_file("/nothing/file", b"")),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            kind(fs.write_file("/canvas/file", b"")),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            kind(fs.write_file("/layers", b"")),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            kind(fs.write_file(format!("/{}", "a".repeat(32)), b"")),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}